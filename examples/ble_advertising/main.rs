@@ -78,7 +78,7 @@ fn main() -> ! {
     let timers = dp.TIMER.split();
     wifi_timer_init(timers.channel0, dp.HBN);
 
-    wifi_init();
+    wifi_init(WifiConfig::new()).unwrap();
 
     for _ in 0..20000 {}
 
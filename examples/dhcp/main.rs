@@ -14,14 +14,10 @@ use hal::{
     prelude::*,
     serial::*,
 };
-use smoltcp::{
-    dhcp::Dhcpv4Client,
-    iface::{NeighborCache, Routes},
-    socket::{RawPacketMetadata, RawSocketBuffer, TcpSocket, TcpSocketBuffer},
-    wire::{EthernetAddress, IpCidr, Ipv4Address, Ipv4Cidr},
-};
+use smoltcp::socket::{SocketSet, TcpSocket, TcpSocketBuffer};
 
 use bl602_hal::timer::TimerExt;
+use embedded_time::duration::Milliseconds;
 
 static mut GLOBAL_SERIAL: MaybeUninit<
     bl602_hal::serial::Serial<
@@ -35,7 +31,7 @@ static mut GLOBAL_SERIAL: MaybeUninit<
 
 use bl602wifi::log::set_writer;
 use bl602wifi::println;
-use bl602wifi::timer::{timestamp, wifi_timer_init};
+use bl602wifi::timer::wifi_timer_init;
 use bl602wifi::wifi::*;
 
 mod wifi_config;
@@ -80,45 +76,16 @@ fn main() -> ! {
     let timers = dp.TIMER.split();
     wifi_timer_init(timers.channel0, dp.HBN);
 
-    let mut socket_set_entries: [_; 2] = Default::default();
-    let mut sockets = smoltcp::socket::SocketSet::new(&mut socket_set_entries[..]);
-    let mut neighbor_cache_storage = [None; 8];
-    let neighbor_cache = NeighborCache::new(&mut neighbor_cache_storage[..]);
-
-    let hw_address = EthernetAddress::from_bytes(&[0, 0, 0, 0, 0, 0]);
-    let device = WifiDevice::new();
-
-    let ip_addr = IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0);
-    let mut ip_addrs = [ip_addr];
-
-    let mut dhcp_rx_buff = [0u8; 900];
-    let mut dhcp_rx_metadata_storage = [RawPacketMetadata::EMPTY; 1];
-    let dhcp_rx_buffer =
-        RawSocketBuffer::new(&mut dhcp_rx_metadata_storage[..], &mut dhcp_rx_buff[..]);
-    let mut dhcp_tx_buff = [0u8; 600];
-    let mut dhcp_tx_metadata_storage = [RawPacketMetadata::EMPTY; 1];
-    let dhcp_tx_buffer =
-        RawSocketBuffer::new(&mut dhcp_tx_metadata_storage[..], &mut dhcp_tx_buff[..]);
-    let mut dhcp = Dhcpv4Client::new(&mut sockets, dhcp_rx_buffer, dhcp_tx_buffer, timestamp());
-    let mut prev_cidr = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
-
-    let mut routes_storage = [None; 1];
-    let routes = Routes::new(&mut routes_storage[..]);
-
-    let mut ethernet = smoltcp::iface::EthernetInterfaceBuilder::new(device)
-        .ethernet_addr(hw_address)
-        .neighbor_cache(neighbor_cache)
-        .ip_addrs(&mut ip_addrs[..])
-        .routes(routes)
-        .finalize();
+    wifi_init(WifiConfig::new().hostname("bl602")).unwrap();
 
-    wifi_init();
-
-    init_mac(&mut ethernet);
+    let mut socket_set_entries: [_; 2] = Default::default();
+    let sockets = SocketSet::new(&mut socket_set_entries[..]);
+    let mut resources = NetworkResources::new();
+    let mut stack = NetworkStack::new(&mut resources, sockets);
 
     println!("start connect");
 
-    connect_sta(WIFI_SSID, WIFI_PASSWORD);
+    let mut connecting = connect_async(WIFI_SSID, WIFI_PASSWORD, Some(Milliseconds(15_000))).ok();
 
     let greet_socket = {
         static mut TCP_SERVER_RX_DATA: [u8; 32] = [0; 32];
@@ -129,63 +96,35 @@ fn main() -> ! {
 
         TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer)
     };
-    let greet_handle = sockets.add(greet_socket);
+    let greet_handle = stack.sockets().add(greet_socket);
+    let mut prev_config = IpConfig::default();
 
     // task should never return
     loop {
-        let timestamp = timestamp();
-        riscv::interrupt::free(|_| {
-            ethernet.poll(&mut sockets, timestamp).ok();
-        });
-
-        trigger_transmit_if_needed();
-
-        let config = dhcp
-            .poll(&mut ethernet, &mut sockets, timestamp)
-            .unwrap_or_else(|e| {
-                println!("DHCP: {:?}", e);
-                None
-            });
-
-        config.map(|config| {
-            println!("DHCP config: {:?}", config);
-            if let Some(cidr) = config.address {
-                if cidr != prev_cidr {
-                    ethernet.update_ip_addrs(|addrs| {
-                        addrs.iter_mut().next().map(|addr| {
-                            *addr = IpCidr::Ipv4(cidr);
-                        });
-                    });
-                    prev_cidr = cidr;
-                    println!("Assigned a new IPv4 address: {}", cidr);
+        stack.poll();
+
+        // the stack has to be polled while connecting to run DHCP
+        if let Some(handle) = connecting.as_mut() {
+            match handle.poll() {
+                Ok(()) => connecting = None,
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(err)) => {
+                    println!("connect failed: {:?} {:?}", err, err.status_str());
+                    connecting =
+                        connect_async(WIFI_SSID, WIFI_PASSWORD, Some(Milliseconds(15_000))).ok();
                 }
             }
+        }
 
-            config.router.map(|router| {
-                ethernet
-                    .routes_mut()
-                    .add_default_ipv4_route(router)
-                    .unwrap()
-            });
-            ethernet.routes_mut().update(|routes_map| {
-                routes_map
-                    .get(&IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0))
-                    .map(|default_route| {
-                        println!("Default gateway: {}", default_route.via_router);
-                    });
-            });
-
-            if config.dns_servers.iter().any(|s| s.is_some()) {
-                println!("DNS servers:");
-                for dns_server in config.dns_servers.iter().filter_map(|s| *s) {
-                    println!("- {}", dns_server);
-                }
-            }
-        });
+        let config = stack.ip_config();
+        if config != prev_config {
+            println!("IP config: {:?}", config);
+            prev_config = config;
+        }
 
         // Control the "greeting" socket (:4321)
         {
-            let mut socket = sockets.get::<TcpSocket>(greet_handle);
+            let mut socket = stack.sockets().get::<TcpSocket>(greet_handle);
             if !socket.is_open() {
                 println!(
                     "Listening to port 4321 for greeting, \
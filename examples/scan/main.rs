@@ -27,10 +27,10 @@ static mut GLOBAL_SERIAL: MaybeUninit<
     >,
 > = MaybeUninit::uninit();
 
+use bl602wifi::log::set_writer;
 use bl602wifi::println;
 use bl602wifi::timer::wifi_timer_init;
 use bl602wifi::wifi::*;
-use bl602wifi::{compat::common::StrBuf, log::set_writer};
 
 #[riscv_rt::entry]
 fn main() -> ! {
@@ -70,30 +70,28 @@ fn main() -> ! {
     let timers = dp.TIMER.split();
     wifi_timer_init(timers.channel0, dp.HBN);
 
-    wifi_init();
+    wifi_init(WifiConfig::new()).unwrap();
 
-    for _ in 0..150000 {}
-
-    let scan_result = wifi_scan();
+    let scan_result = scan(&ScanConfig::default());
 
     match scan_result {
         Ok(scan_result) => {
             for item in &scan_result {
                 match item {
                     Some(item) => {
-                        let ssid = unsafe { StrBuf::from(&item.ssid as *const u8) };
-                        println!("SSID: {}", ssid.as_str_ref());
+                        println!("SSID: {}", item.ssid_str());
                         println!("BSSID: {:x?}", item.bssid);
                         println!("CHANNEL: {}", item.channel);
                         println!("RSSI: {}", item.rssi);
+                        println!("AUTH: {:?} {:?}", item.auth, item.cipher);
                         println!("");
                     }
                     None => (),
                 }
             }
         }
-        Err(_) => {
-            println!("Some error occured")
+        Err(err) => {
+            println!("Scan failed {:?}", err)
         }
     }
 
@@ -21,6 +21,7 @@ use smoltcp::{
 };
 
 use bl602_hal::timer::TimerExt;
+use embedded_time::duration::Milliseconds;
 
 static mut GLOBAL_SERIAL: MaybeUninit<
     bl602_hal::serial::Serial<
@@ -103,13 +104,18 @@ fn main() -> ! {
         .routes(routes)
         .finalize();
 
-    wifi_init();
+    wifi_init(WifiConfig::new()).unwrap();
 
     init_mac(&mut ethernet);
 
     println!("start connect");
 
-    connect_sta(WIFI_SSID, WIFI_PASSWORD);
+    while let Err(err) = connect(WIFI_SSID, WIFI_PASSWORD, Some(Milliseconds(15_000))) {
+        println!("connect failed: {:?} {:?}", err, err.status_str());
+    }
+
+    let mut reconnect =
+        ReconnectSupervisor::new(WIFI_SSID, WIFI_PASSWORD, ReconnectConfig::default()).unwrap();
 
     let greet_socket = {
         static mut TCP_SERVER_RX_DATA: [u8; 32] = [0; 32];
@@ -131,6 +137,8 @@ fn main() -> ! {
 
         trigger_transmit_if_needed();
 
+        reconnect.poll();
+
         // Control the "greeting" socket (:4321)
         {
             let mut socket = sockets.get::<TcpSocket>(greet_handle);
@@ -14,7 +14,7 @@ MEMORY
     /* ITCM      (wxa) : ORIGIN = 0x22008000, LENGTH = 48K */
     DTCM      (wxa) : ORIGIN = 0x42010000, LENGTH = (32K + 48K + 64K - 16K) /* itcm_32 + dtcm_48 + ocram_64 */
     XIP_FLASH (rwx) : ORIGIN = 0x23000000, LENGTH = 4M
-    WIFI_RAM  (wxa) : ORIGIN = 0x42030000, LENGTH = (112K - 8K) /* 8K left for em */
+    WIFI_RAM  (wxa) : ORIGIN = 0x42030000, LENGTH = (112K - 8K - 1K) /* 8K left for em, 1K for boot2 */
 }
 
 REGION_ALIAS("REGION_TEXT", XIP_FLASH);
@@ -30,6 +30,14 @@ PROVIDE(_max_hart_id = 0);
 PROVIDE(_hart_stack_size = 2K);
 PROVIDE(_heap_size = 16k);
 
+/* boot2 hands over the partition table and the flash configuration of the boot header here */
+PROVIDE(__boot2_pt_addr_src = 0x42049C00);
+PROVIDE(__boot2_flash_cfg_src = 0x42049C18);
+
+/* flash region of storage::XipFlash::reserved(), the last 16K of a 2M flash */
+PROVIDE(__kvstore_flash_offset = 0x1FC000);
+PROVIDE(__kvstore_flash_size = 16K);
+
 PROVIDE(UserSoft = DefaultHandler);
 PROVIDE(SupervisorSoft = DefaultHandler);
 PROVIDE(MachineSoft = DefaultHandler);
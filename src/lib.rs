@@ -7,5 +7,7 @@ pub mod log;
 pub mod os_adapter;
 #[allow(non_camel_case_types, non_snake_case)]
 pub mod preemt;
+pub mod rfparams;
+pub mod storage;
 pub mod timer;
 pub mod wifi;
@@ -0,0 +1,58 @@
+//! The RF calibration parameters linked in from rfparams.ld.
+//!
+//! `tools/rfparams` generates rfparams.ld from a TOML description, per board
+//! crystal calibration can be applied at runtime via [set_capcode].
+
+mod tlv;
+pub use tlv::{
+    Entries, Entry, Format, RfParams, RfParamsError, RfParamsWriter, Tag, MAGIC, RFPARAMS_LEN, TAGS,
+};
+
+use crate::println;
+
+/// Largest crystal capcode
+pub const CAPCODE_MAX: u8 = 0x3f;
+
+extern "C" {
+    // see memory.x
+    static _ld_symbol_rftlv_address: u8;
+
+    fn hal_sys_capcode_update(capin: u8, capout: u8) -> i32;
+}
+
+/// The rfparams in flash
+pub fn rfparams() -> Result<RfParams<'static>, RfParamsError> {
+    let data = unsafe {
+        core::slice::from_raw_parts(&_ld_symbol_rftlv_address as *const u8, RFPARAMS_LEN)
+    };
+    RfParams::parse(data)
+}
+
+/// Prints the entries in the format of the TOML description
+pub fn print_rfparams(params: &RfParams) {
+    for entry in params.entries().filter_map(|entry| entry.ok()) {
+        println!("{}", entry);
+    }
+}
+
+/// Overrides the crystal load capacitance, e.g. with a value calibrated per board
+pub fn set_capcode(capin: u8, capout: u8) -> Result<(), RfParamsError> {
+    if capin > CAPCODE_MAX || capout > CAPCODE_MAX {
+        return Err(RfParamsError::InvalidCapcode);
+    }
+
+    let res = unsafe { hal_sys_capcode_update(capin, capout) };
+    if res != 0 {
+        return Err(RfParamsError::Failed(res));
+    }
+
+    Ok(())
+}
+
+/// Applies the capcode of the `xtal` entry
+pub fn apply_capcode(params: &RfParams) -> Result<(), RfParamsError> {
+    let (capin, capout) = params
+        .capcode()
+        .ok_or(RfParamsError::MissingEntry(Tag::Xtal))?;
+    set_capcode(capin, capout)
+}
@@ -0,0 +1,621 @@
+//! The rfparams TLV format: a 16 byte magic followed by entries of a
+//! little endian u16 tag, a u16 length and the value. Tag 0 ends the list.
+//!
+//! Only depends on `core` so the generator in `tools/rfparams` can use it, too.
+
+use core::convert::TryFrom;
+use core::fmt;
+
+pub const MAGIC: [u8; 16] = *b"BLRFPARAO6DkXb1k";
+/// Size of the area reserved for the rfparams in flash
+pub const RFPARAMS_LEN: usize = 2048;
+
+const ENTRY_HEADER_LEN: usize = 4;
+const TAG_END: u16 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RfParamsError {
+    /// The data doesn't start with `MAGIC`
+    InvalidMagic,
+    /// The entry at the offset exceeds the data
+    Truncated { offset: usize },
+    /// The value of a known tag has an unexpected length
+    InvalidLength { tag: u16, len: usize },
+    /// There is no end tag
+    MissingEnd,
+    /// The entry isn't present
+    MissingEntry(Tag),
+    /// Capcodes are 6 bits wide
+    InvalidCapcode,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+/// How the value of a tag is encoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Str,
+    U8,
+    /// Little endian
+    U16,
+    /// Little endian
+    U32,
+    /// Layout unknown to us, shown as bytes
+    Opaque,
+}
+
+impl Format {
+    pub fn width(&self) -> usize {
+        match self {
+            Format::Str | Format::U8 | Format::Opaque => 1,
+            Format::U16 => 2,
+            Format::U32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tag {
+    /// Crystal mode, e.g. "MF"
+    XtalMode,
+    /// Capcode in, capcode out, ready, inbuf, osc
+    Xtal,
+    /// Power mode, e.g. "bf"
+    PwrMode,
+    PwrTable,
+    /// TX power of the 11b rates in dBm
+    PwrTable11b,
+    /// TX power of the 11g rates in dBm
+    PwrTable11g,
+    /// TX power of the 11n rates in dBm
+    PwrTable11n,
+    /// Power offset of channels 1 - 14, 10 means no offset
+    PwrOffset,
+    ChanDivTab,
+    ChanCntTab,
+    LoFcalDiv,
+    /// Enables temperature calibration
+    EnTcal,
+    LinearOrFollow,
+    /// Frequencies of the temperature calibration channels in MHz
+    Tchannels,
+    TchannelOs,
+    TchannelOsLow,
+    TroomOs,
+    /// BLE TX power in dBm
+    PwrTableBle,
+}
+
+pub const TAGS: [Tag; 18] = [
+    Tag::XtalMode,
+    Tag::Xtal,
+    Tag::PwrMode,
+    Tag::PwrTable,
+    Tag::PwrTable11b,
+    Tag::PwrTable11g,
+    Tag::PwrTable11n,
+    Tag::PwrOffset,
+    Tag::ChanDivTab,
+    Tag::ChanCntTab,
+    Tag::LoFcalDiv,
+    Tag::EnTcal,
+    Tag::LinearOrFollow,
+    Tag::Tchannels,
+    Tag::TchannelOs,
+    Tag::TchannelOsLow,
+    Tag::TroomOs,
+    Tag::PwrTableBle,
+];
+
+impl Tag {
+    pub fn from_raw(raw: u16) -> Option<Tag> {
+        TAGS.iter().copied().find(|tag| tag.to_raw() == raw)
+    }
+
+    pub fn to_raw(self) -> u16 {
+        match self {
+            Tag::XtalMode => 0x01,
+            Tag::Xtal => 0x02,
+            Tag::PwrMode => 0x03,
+            Tag::PwrTable => 0x04,
+            Tag::PwrTable11b => 0x05,
+            Tag::PwrTable11g => 0x06,
+            Tag::PwrTable11n => 0x07,
+            Tag::PwrOffset => 0x08,
+            Tag::ChanDivTab => 0x09,
+            Tag::ChanCntTab => 0x0a,
+            Tag::LoFcalDiv => 0x0b,
+            Tag::EnTcal => 0x20,
+            Tag::LinearOrFollow => 0x21,
+            Tag::Tchannels => 0x22,
+            Tag::TchannelOs => 0x23,
+            Tag::TchannelOsLow => 0x24,
+            Tag::TroomOs => 0x25,
+            Tag::PwrTableBle => 0x30,
+        }
+    }
+
+    /// The name used by the TOML description
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tag::XtalMode => "xtal_mode",
+            Tag::Xtal => "xtal",
+            Tag::PwrMode => "pwr_mode",
+            Tag::PwrTable => "pwr_table",
+            Tag::PwrTable11b => "pwr_table_11b",
+            Tag::PwrTable11g => "pwr_table_11g",
+            Tag::PwrTable11n => "pwr_table_11n",
+            Tag::PwrOffset => "pwr_offset",
+            Tag::ChanDivTab => "chan_div_tab",
+            Tag::ChanCntTab => "chan_cnt_tab",
+            Tag::LoFcalDiv => "lo_fcal_div",
+            Tag::EnTcal => "en_tcal",
+            Tag::LinearOrFollow => "linear_or_follow",
+            Tag::Tchannels => "tchannels",
+            Tag::TchannelOs => "tchannel_os",
+            Tag::TchannelOsLow => "tchannel_os_low",
+            Tag::TroomOs => "troom_os",
+            Tag::PwrTableBle => "pwr_table_ble",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Tag> {
+        TAGS.iter().copied().find(|tag| tag.name() == name)
+    }
+
+    pub fn format(&self) -> Format {
+        match self {
+            Tag::XtalMode | Tag::PwrMode => Format::Str,
+            Tag::PwrTable11b
+            | Tag::PwrTable11g
+            | Tag::PwrTable11n
+            | Tag::PwrOffset
+            | Tag::EnTcal
+            | Tag::LinearOrFollow => Format::U8,
+            Tag::Tchannels | Tag::TchannelOs | Tag::TchannelOsLow | Tag::TroomOs => Format::U16,
+            Tag::Xtal | Tag::PwrTableBle => Format::U32,
+            Tag::PwrTable | Tag::ChanDivTab | Tag::ChanCntTab | Tag::LoFcalDiv => Format::Opaque,
+        }
+    }
+
+    /// Number of elements, `None` if it's not fixed
+    pub fn count(&self) -> Option<usize> {
+        match self {
+            Tag::XtalMode | Tag::PwrMode => Some(2),
+            Tag::Xtal | Tag::Tchannels | Tag::TchannelOs | Tag::TchannelOsLow => Some(5),
+            Tag::PwrTable11b => Some(4),
+            Tag::PwrTable11g | Tag::PwrTable11n => Some(8),
+            Tag::PwrOffset => Some(14),
+            Tag::EnTcal | Tag::LinearOrFollow | Tag::TroomOs | Tag::PwrTableBle => Some(1),
+            Tag::PwrTable | Tag::ChanDivTab | Tag::ChanCntTab | Tag::LoFcalDiv => None,
+        }
+    }
+
+    /// Checks the length of a value
+    pub fn validate_len(&self, len: usize) -> Result<(), RfParamsError> {
+        let width = self.format().width();
+        let valid = match self.count() {
+            Some(count) => len == count * width,
+            None => true,
+        };
+
+        if !valid {
+            return Err(RfParamsError::InvalidLength {
+                tag: self.to_raw(),
+                len,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    pub tag: u16,
+    pub value: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    pub fn kind(&self) -> Option<Tag> {
+        Tag::from_raw(self.tag)
+    }
+
+    /// The value as u16 or u32 elements
+    pub fn element(&self, index: usize) -> Option<u32> {
+        let width = self.kind()?.format().width();
+        let bytes = self.value.get(index * width..(index + 1) * width)?;
+
+        let mut le = [0u8; 4];
+        le[..width].copy_from_slice(bytes);
+        Some(u32::from_le_bytes(le))
+    }
+}
+
+/// Formats the entry like the TOML description, e.g. `xtal = [36, 36, 0, 60, 60]`
+impl<'a> fmt::Display for Entry<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tag = match self.kind() {
+            Some(tag) => tag,
+            None => {
+                write!(f, "# unknown tag 0x{:02x} =", self.tag)?;
+                for byte in self.value {
+                    write!(f, " {:02x}", byte)?;
+                }
+                return Ok(());
+            }
+        };
+
+        write!(f, "{} = ", tag.name())?;
+
+        if tag.format() == Format::Str {
+            write!(f, "\"")?;
+            for byte in self.value {
+                write!(f, "{}", *byte as char)?;
+            }
+            return write!(f, "\"");
+        }
+
+        let count = self.value.len() / tag.format().width();
+        if tag.count() == Some(1) {
+            return write!(f, "{}", self.element(0).unwrap_or(0));
+        }
+
+        write!(f, "[")?;
+        for index in 0..count {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", self.element(index).unwrap_or(0))?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Iterates the entries, see [RfParams::entries]
+pub struct Entries<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Result<Entry<'a>, RfParamsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let offset = self.offset;
+        let header = match self.data.get(offset..offset + ENTRY_HEADER_LEN) {
+            Some(header) => header,
+            None => {
+                self.done = true;
+                return Some(Err(RfParamsError::MissingEnd));
+            }
+        };
+
+        let tag = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        if tag == TAG_END {
+            self.done = true;
+            return None;
+        }
+
+        let start = offset + ENTRY_HEADER_LEN;
+        let value = match self.data.get(start..start + len) {
+            Some(value) => value,
+            None => {
+                self.done = true;
+                return Some(Err(RfParamsError::Truncated { offset }));
+            }
+        };
+        self.offset = start + len;
+
+        if let Some(kind) = Tag::from_raw(tag) {
+            if let Err(err) = kind.validate_len(len) {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+
+        Some(Ok(Entry { tag, value }))
+    }
+}
+
+/// Validated rfparams
+#[derive(Clone, Copy)]
+pub struct RfParams<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> RfParams<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<RfParams<'a>, RfParamsError> {
+        if data.get(..MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err(RfParamsError::InvalidMagic);
+        }
+
+        let params = RfParams { data };
+        for entry in params.entries() {
+            entry?;
+        }
+
+        Ok(params)
+    }
+
+    pub fn entries(&self) -> Entries<'a> {
+        Entries {
+            data: self.data,
+            offset: MAGIC.len(),
+            done: false,
+        }
+    }
+
+    /// The first entry of the tag
+    pub fn get(&self, tag: Tag) -> Option<Entry<'a>> {
+        self.entries()
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.tag == tag.to_raw())
+    }
+
+    pub fn xtal(&self) -> Option<[u32; 5]> {
+        let entry = self.get(Tag::Xtal)?;
+        let mut xtal = [0u32; 5];
+        for (index, value) in xtal.iter_mut().enumerate() {
+            *value = entry.element(index)?;
+        }
+        Some(xtal)
+    }
+
+    /// Crystal capcode in and out
+    pub fn capcode(&self) -> Option<(u8, u8)> {
+        let xtal = self.xtal()?;
+        Some((u8::try_from(xtal[0]).ok()?, u8::try_from(xtal[1]).ok()?))
+    }
+
+    pub fn pwr_offset(&self) -> Option<[u8; 14]> {
+        let entry = self.get(Tag::PwrOffset)?;
+        let mut pwr_offset = [0u8; 14];
+        pwr_offset.copy_from_slice(entry.value);
+        Some(pwr_offset)
+    }
+
+    pub fn tchannels(&self) -> Option<[u16; 5]> {
+        let entry = self.get(Tag::Tchannels)?;
+        let mut tchannels = [0u16; 5];
+        for (index, value) in tchannels.iter_mut().enumerate() {
+            *value = entry.element(index)? as u16;
+        }
+        Some(tchannels)
+    }
+}
+
+/// Prints one entry per line, the output can be fed to the generator
+impl<'a> fmt::Display for RfParams<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries().filter_map(|entry| entry.ok()) {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds rfparams in a zeroed buffer
+pub struct RfParamsWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> RfParamsWriter<'a> {
+    pub fn new(buf: &'a mut [u8]) -> Result<RfParamsWriter<'a>, RfParamsError> {
+        if buf.len() < MAGIC.len() + ENTRY_HEADER_LEN {
+            return Err(RfParamsError::Truncated { offset: 0 });
+        }
+
+        buf.fill(0);
+        buf[..MAGIC.len()].copy_from_slice(&MAGIC);
+        Ok(RfParamsWriter {
+            buf,
+            len: MAGIC.len(),
+        })
+    }
+
+    pub fn entry(&mut self, tag: Tag, value: &[u8]) -> Result<&mut Self, RfParamsError> {
+        tag.validate_len(value.len())?;
+
+        // leave room for the end tag
+        let end = self.len + ENTRY_HEADER_LEN + value.len();
+        if end + ENTRY_HEADER_LEN > self.buf.len() || value.len() > u16::MAX as usize {
+            return Err(RfParamsError::Truncated { offset: self.len });
+        }
+
+        self.buf[self.len..][..2].copy_from_slice(&tag.to_raw().to_le_bytes());
+        self.buf[self.len + 2..][..2].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        self.buf[self.len + ENTRY_HEADER_LEN..end].copy_from_slice(value);
+        self.len = end;
+
+        Ok(self)
+    }
+
+    /// Length including the end tag, the rest of the buffer stays zero
+    pub fn finish(self) -> usize {
+        self.len + ENTRY_HEADER_LEN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    const XTAL: [u8; 20] = [
+        36, 0, 0, 0, 36, 0, 0, 0, 0, 0, 0, 0, 60, 0, 0, 0, 60, 0, 0, 0,
+    ];
+
+    /// Formats into a fixed buffer, the module only depends on `core`
+    struct Line {
+        buf: [u8; 64],
+        len: usize,
+    }
+
+    impl Line {
+        fn new() -> Line {
+            Line {
+                buf: [0u8; 64],
+                len: 0,
+            }
+        }
+
+        fn as_str(&self) -> &str {
+            core::str::from_utf8(&self.buf[..self.len]).unwrap()
+        }
+    }
+
+    impl fmt::Write for Line {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            if end > self.buf.len() {
+                return Err(fmt::Error);
+            }
+            self.buf[self.len..end].copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    fn to_line(entry: Entry) -> Line {
+        let mut line = Line::new();
+        write!(line, "{}", entry).unwrap();
+        line
+    }
+
+    fn header(buf: &mut [u8], tag: u16, len: u16) {
+        buf[..2].copy_from_slice(&tag.to_le_bytes());
+        buf[2..4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    #[test]
+    fn tags_round_trip() {
+        for tag in TAGS.iter() {
+            assert_eq!(Tag::from_raw(tag.to_raw()), Some(*tag));
+            assert_eq!(Tag::from_name(tag.name()), Some(*tag));
+        }
+        assert_eq!(Tag::from_raw(TAG_END), None);
+        assert_eq!(Tag::from_name("xtal_capcode"), None);
+    }
+
+    #[test]
+    fn write_and_parse() {
+        let mut buf = [0xffu8; 128];
+        let mut writer = RfParamsWriter::new(&mut buf).unwrap();
+        writer
+            .entry(Tag::XtalMode, b"MF")
+            .unwrap()
+            .entry(Tag::Xtal, &XTAL)
+            .unwrap()
+            .entry(Tag::PwrOffset, &[10; 14])
+            .unwrap()
+            .entry(
+                Tag::Tchannels,
+                &[0x6c, 0x09, 0x85, 0x09, 0x9e, 0x09, 0xb7, 0x09, 0xd0, 0x09],
+            )
+            .unwrap();
+        let len = writer.finish();
+        assert_eq!(len, 16 + 6 + 24 + 18 + 14 + 4);
+        assert!(buf[len..].iter().all(|b| *b == 0));
+
+        let params = RfParams::parse(&buf).unwrap();
+        assert_eq!(params.entries().count(), 4);
+        assert_eq!(params.xtal(), Some([36, 36, 0, 60, 60]));
+        assert_eq!(params.capcode(), Some((36, 36)));
+        assert_eq!(params.pwr_offset(), Some([10; 14]));
+        assert_eq!(params.tchannels(), Some([2412, 2437, 2462, 2487, 2512]));
+        assert!(params.get(Tag::PwrTableBle).is_none());
+    }
+
+    #[test]
+    fn writer_rejects_invalid_entries() {
+        let mut buf = [0u8; 40];
+        let mut writer = RfParamsWriter::new(&mut buf).unwrap();
+        assert_eq!(
+            writer.entry(Tag::Xtal, &XTAL[..16]).err(),
+            Some(RfParamsError::InvalidLength {
+                tag: Tag::Xtal.to_raw(),
+                len: 16
+            })
+        );
+        // 16 bytes magic + 24 bytes entry leave no room for the end tag
+        assert_eq!(
+            writer.entry(Tag::Xtal, &XTAL).err(),
+            Some(RfParamsError::Truncated { offset: 16 })
+        );
+
+        assert!(RfParamsWriter::new(&mut [0u8; 19]).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut buf = [0u8; 32];
+        assert_eq!(
+            RfParams::parse(&buf).err(),
+            Some(RfParamsError::InvalidMagic)
+        );
+
+        buf[..16].copy_from_slice(&MAGIC);
+        assert!(RfParams::parse(&buf).is_ok());
+        assert_eq!(
+            RfParams::parse(&buf[..18]).err(),
+            Some(RfParamsError::MissingEnd)
+        );
+
+        header(&mut buf[16..], Tag::PwrOffset.to_raw(), 14);
+        assert_eq!(
+            RfParams::parse(&buf).err(),
+            Some(RfParamsError::Truncated { offset: 16 })
+        );
+
+        header(&mut buf[16..], Tag::PwrOffset.to_raw(), 4);
+        assert_eq!(
+            RfParams::parse(&buf).err(),
+            Some(RfParamsError::InvalidLength {
+                tag: Tag::PwrOffset.to_raw(),
+                len: 4
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_tags_are_kept() {
+        let mut buf = [0u8; 32];
+        buf[..16].copy_from_slice(&MAGIC);
+        header(&mut buf[16..], 0x7f, 3);
+        buf[20..23].copy_from_slice(&[1, 2, 0xab]);
+
+        let params = RfParams::parse(&buf).unwrap();
+        let entry = params.entries().next().unwrap().unwrap();
+        assert_eq!(entry.kind(), None);
+        assert_eq!(entry.element(0), None);
+        assert_eq!(to_line(entry).as_str(), "# unknown tag 0x7f = 01 02 ab");
+    }
+
+    #[test]
+    fn display_like_toml() {
+        let xtal = Entry {
+            tag: Tag::Xtal.to_raw(),
+            value: &XTAL,
+        };
+        assert_eq!(to_line(xtal).as_str(), "xtal = [36, 36, 0, 60, 60]");
+
+        let mode = Entry {
+            tag: Tag::PwrMode.to_raw(),
+            value: b"bf",
+        };
+        assert_eq!(to_line(mode).as_str(), "pwr_mode = \"bf\"");
+
+        let en_tcal = Entry {
+            tag: Tag::EnTcal.to_raw(),
+            value: &[1],
+        };
+        assert_eq!(to_line(en_tcal).as_str(), "en_tcal = 1");
+    }
+}
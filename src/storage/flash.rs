@@ -0,0 +1,86 @@
+/// NOR flash as used by [super::KvStore]. Offsets are relative to the start of
+/// the region, erased bytes read as 0xff and writes can only clear bits.
+pub trait Flash {
+    /// Size of an erase sector in bytes
+    fn sector_size(&self) -> u32;
+
+    /// Number of sectors in the region
+    fn sectors(&self) -> u32;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), FlashError>;
+
+    /// The area has to be erased before
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError>;
+
+    fn erase(&mut self, sector: u32) -> Result<(), FlashError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlashError {
+    /// The access is outside of the region
+    OutOfBounds,
+    /// boot2 didn't hand over the flash configuration
+    NoConfig,
+    /// The ROM driver returned an error
+    Failed(i32),
+}
+
+/// Flash emulated in RAM, e.g. to run the store on the host
+pub struct MemFlash<'a> {
+    data: &'a mut [u8],
+    sector_size: u32,
+}
+
+impl<'a> MemFlash<'a> {
+    /// `data` has to be a multiple of `sector_size`, it's erased by `new`
+    pub fn new(data: &'a mut [u8], sector_size: u32) -> MemFlash<'a> {
+        assert!(sector_size > 0 && data.len() % sector_size as usize == 0);
+
+        data.fill(0xff);
+        MemFlash { data, sector_size }
+    }
+
+    /// The raw content, e.g. to simulate corruption
+    pub fn data(&mut self) -> &mut [u8] {
+        self.data
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<core::ops::Range<usize>, FlashError> {
+        let start = offset as usize;
+        let end = start.checked_add(len).ok_or(FlashError::OutOfBounds)?;
+        if end > self.data.len() {
+            return Err(FlashError::OutOfBounds);
+        }
+        Ok(start..end)
+    }
+}
+
+impl<'a> Flash for MemFlash<'a> {
+    fn sector_size(&self) -> u32 {
+        self.sector_size
+    }
+
+    fn sectors(&self) -> u32 {
+        self.data.len() as u32 / self.sector_size
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), FlashError> {
+        let range = self.range(offset, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+        let range = self.range(offset, data.len())?;
+        for (dst, src) in self.data[range].iter_mut().zip(data.iter()) {
+            *dst &= *src;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, sector: u32) -> Result<(), FlashError> {
+        let range = self.range(sector * self.sector_size, self.sector_size as usize)?;
+        self.data[range].fill(0xff);
+        Ok(())
+    }
+}
@@ -0,0 +1,789 @@
+use super::{Flash, FlashError};
+
+/// Version of the on-flash format, sectors of other versions are discarded
+pub const FORMAT_VERSION: u8 = 1;
+pub const KEY_MAX_LEN: usize = 32;
+
+const SECTOR_MAGIC: [u8; 4] = *b"BLKV";
+// magic, version, 3 reserved bytes, sequence number, CRC
+const SECTOR_HEADER_LEN: u32 = 16;
+// kind, key length, value length, CRC of everything else
+const RECORD_HEADER_LEN: u32 = 8;
+
+const KIND_BLANK: u8 = 0xff;
+const KIND_VALUE: u8 = 0x01;
+const KIND_REMOVED: u8 = 0x02;
+
+const CHUNK_LEN: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KvError {
+    Flash(FlashError),
+    /// Less than two sectors
+    InvalidGeometry,
+    /// The key is empty or longer than `KEY_MAX_LEN`
+    InvalidKey,
+    /// The record doesn't fit into a sector
+    ValueTooLarge,
+    /// The value has the given length
+    BufferTooSmall(usize),
+    /// The live records don't leave room for the new one
+    Full,
+}
+
+impl From<FlashError> for KvError {
+    fn from(err: FlashError) -> Self {
+        KvError::Flash(err)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Record {
+    sector: u32,
+    offset: u32,
+    kind: u8,
+    key_len: u8,
+    value_len: u16,
+    crc_ok: bool,
+}
+
+impl Record {
+    fn len(&self) -> u32 {
+        align4(RECORD_HEADER_LEN + self.key_len as u32 + self.value_len as u32)
+    }
+
+    fn value_offset(&self) -> u32 {
+        self.offset + RECORD_HEADER_LEN + self.key_len as u32
+    }
+}
+
+/// Key-value store in a flash region.
+///
+/// Records are appended to the active sector. When it's full the next sector
+/// becomes active and the live records of the oldest sector are moved over,
+/// so all sectors are written in turn. One sector is always kept erased.
+/// Every record carries a CRC, torn writes after a power loss are ignored.
+pub struct KvStore<F: Flash> {
+    flash: F,
+    active: u32,
+    sequence: u32,
+    write_offset: u32,
+}
+
+impl<F: Flash> KvStore<F> {
+    /// Opens the store, an empty or foreign region gets formatted
+    pub fn mount(flash: F) -> Result<KvStore<F>, KvError> {
+        if flash.sectors() < 2 || flash.sector_size() < SECTOR_HEADER_LEN + RECORD_HEADER_LEN {
+            return Err(KvError::InvalidGeometry);
+        }
+
+        let mut store = KvStore {
+            flash,
+            active: 0,
+            sequence: 0,
+            write_offset: 0,
+        };
+
+        let mut newest: Option<(u32, u32)> = None;
+        for sector in 0..store.flash.sectors() {
+            if let Some(sequence) = store.sector_sequence(sector)? {
+                if newest.map_or(true, |(_, newest)| sequence > newest) {
+                    newest = Some((sector, sequence));
+                }
+            }
+        }
+
+        match newest {
+            Some((sector, sequence)) => {
+                store.active = sector;
+                store.sequence = sequence;
+                store.write_offset = store.end_of_records(sector)?;
+
+                // finish moving the oldest sector if that got interrupted
+                let spare = store.next_sector(sector);
+                if !store.is_blank(spare, 0, store.flash.sector_size())? {
+                    store.compact(spare)?;
+                }
+            }
+            None => store.format()?,
+        }
+
+        Ok(store)
+    }
+
+    /// Erases all records
+    pub fn format(&mut self) -> Result<(), KvError> {
+        for sector in 0..self.flash.sectors() {
+            self.flash.erase(sector)?;
+        }
+
+        self.write_sector_header(0, 1)?;
+        self.active = 0;
+        self.sequence = 1;
+        self.write_offset = SECTOR_HEADER_LEN;
+
+        Ok(())
+    }
+
+    /// Reads the value into `buf` and returns its length
+    pub fn get(&mut self, key: &str, buf: &mut [u8]) -> Result<Option<usize>, KvError> {
+        let record = match self.find(key.as_bytes())? {
+            Some(record) if record.kind == KIND_VALUE => record,
+            _ => return Ok(None),
+        };
+
+        let len = record.value_len as usize;
+        if buf.len() < len {
+            return Err(KvError::BufferTooSmall(len));
+        }
+
+        let offset = self.sector_offset(record.sector) + record.value_offset();
+        self.flash.read(offset, &mut buf[..len])?;
+        Ok(Some(len))
+    }
+
+    /// Like `get` for UTF-8 values
+    pub fn get_str<'b>(
+        &mut self,
+        key: &str,
+        buf: &'b mut [u8],
+    ) -> Result<Option<&'b str>, KvError> {
+        match self.get(key, buf)? {
+            Some(len) => Ok(core::str::from_utf8(&buf[..len]).ok()),
+            None => Ok(None),
+        }
+    }
+
+    pub fn contains(&mut self, key: &str) -> Result<bool, KvError> {
+        Ok(matches!(self.find(key.as_bytes())?, Some(record) if record.kind == KIND_VALUE))
+    }
+
+    pub fn set(&mut self, key: &str, value: &[u8]) -> Result<(), KvError> {
+        if let Some(record) = self.find(key.as_bytes())? {
+            // don't wear the flash when nothing changed
+            if record.kind == KIND_VALUE
+                && record.value_len as usize == value.len()
+                && self.value_equals(&record, value)?
+            {
+                return Ok(());
+            }
+        }
+
+        self.append(KIND_VALUE, key.as_bytes(), value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Result<(), KvError> {
+        match self.find(key.as_bytes())? {
+            Some(record) if record.kind == KIND_VALUE => {
+                self.append(KIND_REMOVED, key.as_bytes(), &[])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the flash, e.g. to mount it again
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    fn append(&mut self, kind: u8, key: &[u8], value: &[u8]) -> Result<(), KvError> {
+        if key.is_empty() || key.len() > KEY_MAX_LEN {
+            return Err(KvError::InvalidKey);
+        }
+
+        let len = align4(RECORD_HEADER_LEN + key.len() as u32 + value.len() as u32);
+        if value.len() > u16::MAX as usize || len > self.flash.sector_size() - SECTOR_HEADER_LEN {
+            return Err(KvError::ValueTooLarge);
+        }
+
+        let mut header = [
+            kind,
+            key.len() as u8,
+            value.len() as u8,
+            (value.len() >> 8) as u8,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let crc = crc32_update(crc32_update(crc32_update(!0, &header[..4]), key), value);
+        header[4..].copy_from_slice(&(!crc).to_le_bytes());
+
+        // every rollover frees the oldest sector, give up after all got compacted
+        for _ in 0..self.flash.sectors() {
+            if self.has_room(len)? {
+                let offset = self.sector_offset(self.active) + self.write_offset;
+                self.flash.write(offset, &header)?;
+                self.flash.write(offset + RECORD_HEADER_LEN, key)?;
+                self.flash
+                    .write(offset + RECORD_HEADER_LEN + key.len() as u32, value)?;
+                self.write_offset += len;
+                return Ok(());
+            }
+
+            self.rollover()?;
+        }
+
+        Err(KvError::Full)
+    }
+
+    fn has_room(&mut self, len: u32) -> Result<bool, KvError> {
+        if self.write_offset + len > self.flash.sector_size() {
+            return Ok(false);
+        }
+
+        // a torn record may have left garbage behind
+        if !self.is_blank(self.active, self.write_offset, len)? {
+            self.write_offset = self.flash.sector_size();
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Makes the erased sector active and frees the oldest one
+    fn rollover(&mut self) -> Result<(), KvError> {
+        let next = self.next_sector(self.active);
+        if !self.is_blank(next, 0, self.flash.sector_size())? {
+            self.flash.erase(next)?;
+        }
+
+        self.sequence += 1;
+        self.write_sector_header(next, self.sequence)?;
+        self.active = next;
+        self.write_offset = SECTOR_HEADER_LEN;
+
+        let oldest = self.next_sector(next);
+        self.compact(oldest)
+    }
+
+    /// Moves the live records of the sector to the active one and erases it
+    fn compact(&mut self, sector: u32) -> Result<(), KvError> {
+        if self.sector_sequence(sector)?.is_some() {
+            let mut offset = SECTOR_HEADER_LEN;
+            while let Some(record) = self.read_record(sector, offset)? {
+                offset += record.len();
+
+                if record.kind != KIND_VALUE || !record.crc_ok {
+                    continue;
+                }
+
+                let mut key = [0u8; KEY_MAX_LEN];
+                let key = self.read_key(&record, &mut key)?;
+                let is_latest = match self.find(key)? {
+                    Some(latest) => latest.sector == sector && latest.offset == record.offset,
+                    None => false,
+                };
+                if !is_latest {
+                    continue;
+                }
+
+                if self.write_offset + record.len() > self.flash.sector_size() {
+                    return Err(KvError::Full);
+                }
+                self.copy_record(&record)?;
+            }
+        }
+
+        self.flash.erase(sector)?;
+        Ok(())
+    }
+
+    fn copy_record(&mut self, record: &Record) -> Result<(), KvError> {
+        let from = self.sector_offset(record.sector) + record.offset;
+        let to = self.sector_offset(self.active) + self.write_offset;
+        let len = RECORD_HEADER_LEN + record.key_len as u32 + record.value_len as u32;
+
+        let mut chunk = [0u8; CHUNK_LEN];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(CHUNK_LEN as u32);
+            self.flash.read(from + done, &mut chunk[..n as usize])?;
+            self.flash.write(to + done, &chunk[..n as usize])?;
+            done += n;
+        }
+
+        self.write_offset += record.len();
+        Ok(())
+    }
+
+    /// The latest record of the key, from the oldest to the active sector
+    fn find(&mut self, key: &[u8]) -> Result<Option<Record>, KvError> {
+        let mut latest = None;
+
+        for i in 0..self.flash.sectors() {
+            let sector = (self.active + 1 + i) % self.flash.sectors();
+            if self.sector_sequence(sector)?.is_none() {
+                continue;
+            }
+
+            let mut offset = SECTOR_HEADER_LEN;
+            while let Some(record) = self.read_record(sector, offset)? {
+                offset += record.len();
+
+                if !record.crc_ok || record.key_len as usize != key.len() {
+                    continue;
+                }
+
+                let mut buf = [0u8; KEY_MAX_LEN];
+                if self.read_key(&record, &mut buf)? == key {
+                    latest = Some(record);
+                }
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// Returns `None` at the end of the records
+    fn read_record(&mut self, sector: u32, offset: u32) -> Result<Option<Record>, KvError> {
+        let sector_size = self.flash.sector_size();
+        if offset + RECORD_HEADER_LEN > sector_size {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; RECORD_HEADER_LEN as usize];
+        self.flash
+            .read(self.sector_offset(sector) + offset, &mut header)?;
+
+        let mut record = Record {
+            sector,
+            offset,
+            kind: header[0],
+            key_len: header[1],
+            value_len: u16::from_le_bytes([header[2], header[3]]),
+            crc_ok: false,
+        };
+        if record.kind == KIND_BLANK || offset + record.len() > sector_size {
+            return Ok(None);
+        }
+
+        let mut crc = crc32_update(!0, &header[..4]);
+        let mut chunk = [0u8; CHUNK_LEN];
+        let mut pos = offset + RECORD_HEADER_LEN;
+        let end = record.value_offset() + record.value_len as u32;
+        while pos < end {
+            let n = (end - pos).min(CHUNK_LEN as u32) as usize;
+            self.flash
+                .read(self.sector_offset(sector) + pos, &mut chunk[..n])?;
+            crc = crc32_update(crc, &chunk[..n]);
+            pos += n as u32;
+        }
+
+        let stored_crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        record.crc_ok = !crc == stored_crc
+            && (record.kind == KIND_VALUE || record.kind == KIND_REMOVED)
+            && record.key_len as usize <= KEY_MAX_LEN;
+
+        Ok(Some(record))
+    }
+
+    fn read_key<'b>(
+        &mut self,
+        record: &Record,
+        buf: &'b mut [u8; KEY_MAX_LEN],
+    ) -> Result<&'b [u8], KvError> {
+        let key = &mut buf[..record.key_len as usize];
+        self.flash.read(
+            self.sector_offset(record.sector) + record.offset + RECORD_HEADER_LEN,
+            key,
+        )?;
+        Ok(key)
+    }
+
+    fn value_equals(&mut self, record: &Record, value: &[u8]) -> Result<bool, KvError> {
+        let offset = self.sector_offset(record.sector) + record.value_offset();
+
+        let mut chunk = [0u8; CHUNK_LEN];
+        for (i, expected) in value.chunks(CHUNK_LEN).enumerate() {
+            let chunk = &mut chunk[..expected.len()];
+            self.flash.read(offset + (i * CHUNK_LEN) as u32, chunk)?;
+            if chunk != expected {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Where appending stops, the sector size if the rest is unusable
+    fn end_of_records(&mut self, sector: u32) -> Result<u32, KvError> {
+        let mut offset = SECTOR_HEADER_LEN;
+        while let Some(record) = self.read_record(sector, offset)? {
+            offset += record.len();
+        }
+
+        let sector_size = self.flash.sector_size();
+        if offset + RECORD_HEADER_LEN <= sector_size
+            && !self.is_blank(sector, offset, RECORD_HEADER_LEN)?
+        {
+            // the header of a torn record points past the end of the sector
+            return Ok(sector_size);
+        }
+
+        Ok(offset)
+    }
+
+    /// The sequence number if the sector has a valid header
+    fn sector_sequence(&mut self, sector: u32) -> Result<Option<u32>, KvError> {
+        let mut header = [0u8; SECTOR_HEADER_LEN as usize];
+        self.flash.read(self.sector_offset(sector), &mut header)?;
+
+        let crc = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+        if header[..4] != SECTOR_MAGIC
+            || header[4] != FORMAT_VERSION
+            || !crc32_update(!0, &header[..12]) != crc
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(u32::from_le_bytes([
+            header[8], header[9], header[10], header[11],
+        ])))
+    }
+
+    fn write_sector_header(&mut self, sector: u32, sequence: u32) -> Result<(), KvError> {
+        let mut header = [0xffu8; SECTOR_HEADER_LEN as usize];
+        header[..4].copy_from_slice(&SECTOR_MAGIC);
+        header[4] = FORMAT_VERSION;
+        header[8..12].copy_from_slice(&sequence.to_le_bytes());
+        let crc = !crc32_update(!0, &header[..12]);
+        header[12..].copy_from_slice(&crc.to_le_bytes());
+
+        self.flash.write(self.sector_offset(sector), &header)?;
+        Ok(())
+    }
+
+    fn is_blank(&mut self, sector: u32, offset: u32, len: u32) -> Result<bool, KvError> {
+        let start = self.sector_offset(sector) + offset;
+
+        let mut chunk = [0u8; CHUNK_LEN];
+        let mut done = 0;
+        while done < len {
+            let n = (len - done).min(CHUNK_LEN as u32) as usize;
+            self.flash.read(start + done, &mut chunk[..n])?;
+            if chunk[..n].iter().any(|b| *b != 0xff) {
+                return Ok(false);
+            }
+            done += n as u32;
+        }
+
+        Ok(true)
+    }
+
+    fn next_sector(&self, sector: u32) -> u32 {
+        (sector + 1) % self.flash.sectors()
+    }
+
+    fn sector_offset(&self, sector: u32) -> u32 {
+        sector * self.flash.sector_size()
+    }
+}
+
+fn align4(len: u32) -> u32 {
+    (len + 3) & !3
+}
+
+/// CRC-32 (IEEE), start with `!0` and invert the result
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemFlash;
+
+    const SECTOR_SIZE: u32 = 256;
+
+    const KEY_SSID: &str = "wifi.ssid";
+    const KEY_NAME: &str = "device.name";
+    const KEY_COUNTER: &str = "counter";
+
+    fn get<F: Flash>(store: &mut KvStore<F>, key: &str) -> Option<([u8; 128], usize)> {
+        let mut buf = [0u8; 128];
+        store.get(key, &mut buf).unwrap().map(|len| (buf, len))
+    }
+
+    fn assert_value<F: Flash>(store: &mut KvStore<F>, key: &str, expected: Option<&[u8]>) {
+        let value = get(store, key);
+        assert_eq!(value.as_ref().map(|(buf, len)| &buf[..*len]), expected);
+    }
+
+    /// Fails every operation after the given number, the failing write only
+    /// gets half of the data to the flash
+    struct PowerLossFlash<'a> {
+        flash: MemFlash<'a>,
+        operations_left: Option<usize>,
+    }
+
+    impl<'a> PowerLossFlash<'a> {
+        fn operation(&mut self) -> Result<(), FlashError> {
+            match self.operations_left.as_mut() {
+                Some(0) => Err(FlashError::Failed(-1)),
+                Some(left) => {
+                    *left -= 1;
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl<'a> Flash for PowerLossFlash<'a> {
+        fn sector_size(&self) -> u32 {
+            self.flash.sector_size()
+        }
+
+        fn sectors(&self) -> u32 {
+            self.flash.sectors()
+        }
+
+        fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), FlashError> {
+            self.flash.read(offset, buf)
+        }
+
+        fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+            if self.operations_left == Some(0) {
+                self.flash.write(offset, &data[..data.len() / 2])?;
+            }
+            self.operation()?;
+            self.flash.write(offset, data)
+        }
+
+        fn erase(&mut self, sector: u32) -> Result<(), FlashError> {
+            self.operation()?;
+            self.flash.erase(sector)
+        }
+    }
+
+    #[test]
+    fn set_get_remove() {
+        let mut data = [0u8; 2 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+
+        store.set(KEY_SSID, b"lab").unwrap();
+        store.set(KEY_NAME, b"sensor").unwrap();
+        store.set(KEY_SSID, b"office").unwrap();
+        assert_value(&mut store, KEY_SSID, Some(b"office"));
+        assert_value(&mut store, KEY_NAME, Some(b"sensor"));
+        assert!(store.contains(KEY_NAME).unwrap());
+
+        let mut buf = [0u8; 8];
+        assert_eq!(store.get_str(KEY_NAME, &mut buf), Ok(Some("sensor")));
+        assert_eq!(
+            store.get(KEY_NAME, &mut buf[..3]),
+            Err(KvError::BufferTooSmall(6))
+        );
+
+        store.remove(KEY_NAME).unwrap();
+        assert_value(&mut store, KEY_NAME, None);
+        assert!(!store.contains(KEY_NAME).unwrap());
+        assert_value(&mut store, "missing", None);
+
+        assert_eq!(store.set("", b"x"), Err(KvError::InvalidKey));
+        let long_key = core::str::from_utf8(&[b'k'; KEY_MAX_LEN + 1]).unwrap();
+        assert_eq!(store.set(long_key, b"x"), Err(KvError::InvalidKey));
+        assert_eq!(
+            store.set(KEY_NAME, &[0u8; SECTOR_SIZE as usize]),
+            Err(KvError::ValueTooLarge)
+        );
+    }
+
+    #[test]
+    fn invalid_geometry() {
+        let mut data = [0u8; SECTOR_SIZE as usize];
+        assert_eq!(
+            KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).err(),
+            Some(KvError::InvalidGeometry)
+        );
+    }
+
+    #[test]
+    fn remount() {
+        let mut data = [0u8; 3 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+        store.set(KEY_SSID, b"lab").unwrap();
+        store.set(KEY_NAME, b"sensor").unwrap();
+        store.remove(KEY_NAME).unwrap();
+
+        let mut store = KvStore::mount(store.release()).unwrap();
+        assert_value(&mut store, KEY_SSID, Some(b"lab"));
+        assert_value(&mut store, KEY_NAME, None);
+
+        store.set(KEY_NAME, b"again").unwrap();
+        let mut store = KvStore::mount(store.release()).unwrap();
+        assert_value(&mut store, KEY_NAME, Some(b"again"));
+    }
+
+    #[test]
+    fn rollover_keeps_live_records() {
+        for sectors in 2..=4 {
+            let mut data = [0u8; 4 * SECTOR_SIZE as usize];
+            let data = &mut data[..sectors * SECTOR_SIZE as usize];
+            let mut store = KvStore::mount(MemFlash::new(data, SECTOR_SIZE)).unwrap();
+            store.set(KEY_SSID, b"lab").unwrap();
+
+            // many times the capacity, every sector gets written and compacted
+            for i in 0..500u32 {
+                store.set(KEY_COUNTER, &i.to_le_bytes()).unwrap();
+                assert_value(&mut store, KEY_COUNTER, Some(&i.to_le_bytes()));
+                assert_value(&mut store, KEY_SSID, Some(b"lab"));
+            }
+
+            let mut store = KvStore::mount(store.release()).unwrap();
+            assert_value(&mut store, KEY_COUNTER, Some(&499u32.to_le_bytes()));
+            assert_value(&mut store, KEY_SSID, Some(b"lab"));
+        }
+    }
+
+    #[test]
+    fn unchanged_value_isnt_written() {
+        let mut data = [0u8; 2 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+        store.set(KEY_SSID, b"lab").unwrap();
+
+        let mut flash = store.release();
+        let mut snapshot = [0u8; 2 * SECTOR_SIZE as usize];
+        snapshot.copy_from_slice(flash.data());
+
+        let mut store = KvStore::mount(flash).unwrap();
+        for _ in 0..100 {
+            store.set(KEY_SSID, b"lab").unwrap();
+        }
+        assert_eq!(store.release().data(), &snapshot[..]);
+    }
+
+    #[test]
+    fn torn_record_is_ignored() {
+        let mut data = [0u8; 2 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+        store.set(KEY_SSID, b"lab").unwrap();
+        let end = store.write_offset as usize;
+        store.set(KEY_SSID, b"office").unwrap();
+
+        // the value of the latest record didn't make it to the flash
+        let mut flash = store.release();
+        for byte in flash.data()[end + RECORD_HEADER_LEN as usize + KEY_SSID.len()..].iter_mut() {
+            *byte = 0xff;
+        }
+        let mut store = KvStore::mount(flash).unwrap();
+        assert_value(&mut store, KEY_SSID, Some(b"lab"));
+
+        // appending continues behind the garbage
+        store.set(KEY_NAME, b"sensor").unwrap();
+        let mut store = KvStore::mount(store.release()).unwrap();
+        assert_value(&mut store, KEY_SSID, Some(b"lab"));
+        assert_value(&mut store, KEY_NAME, Some(b"sensor"));
+    }
+
+    #[test]
+    fn full() {
+        let mut data = [0u8; 2 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+
+        store.set("a", &[1u8; 100]).unwrap();
+        store.set("b", &[2u8; 100]).unwrap();
+        assert_eq!(store.set("c", &[3u8; 100]), Err(KvError::Full));
+
+        assert_value(&mut store, "a", Some(&[1u8; 100]));
+        assert_value(&mut store, "b", Some(&[2u8; 100]));
+        assert_value(&mut store, "c", None);
+
+        // room again after removing a value
+        store.remove("a").unwrap();
+        store.set("c", &[3u8; 100]).unwrap();
+        assert_value(&mut store, "c", Some(&[3u8; 100]));
+    }
+
+    #[test]
+    fn foreign_format_version_is_discarded() {
+        let mut data = [0u8; 2 * SECTOR_SIZE as usize];
+        let mut store = KvStore::mount(MemFlash::new(&mut data, SECTOR_SIZE)).unwrap();
+        store.set(KEY_SSID, b"lab").unwrap();
+
+        // a valid sector header of another version
+        let mut flash = store.release();
+        let header = &mut flash.data()[..SECTOR_HEADER_LEN as usize];
+        header[4] = FORMAT_VERSION + 1;
+        let crc = !crc32_update(!0, &header[..12]);
+        header[12..].copy_from_slice(&crc.to_le_bytes());
+
+        let mut store = KvStore::mount(flash).unwrap();
+        assert_value(&mut store, KEY_SSID, None);
+        store.set(KEY_SSID, b"office").unwrap();
+        assert_value(&mut store, KEY_SSID, Some(b"office"));
+    }
+
+    #[test]
+    fn power_loss() {
+        for sectors in 2..=3 {
+            let len = sectors * SECTOR_SIZE as usize;
+            let mut base = [0u8; 3 * SECTOR_SIZE as usize];
+            {
+                let mut store =
+                    KvStore::mount(MemFlash::new(&mut base[..len], SECTOR_SIZE)).unwrap();
+                store.set(KEY_SSID, b"lab").unwrap();
+            }
+
+            for step in 0..60u8 {
+                let value = [step; 37];
+
+                // cut the power at every write or erase of the update
+                for cut in 0.. {
+                    let mut data = base;
+                    let mut flash = MemFlash::new(&mut data[..len], SECTOR_SIZE);
+                    flash.data().copy_from_slice(&base[..len]);
+                    let mut store = KvStore::mount(PowerLossFlash {
+                        flash,
+                        operations_left: Some(cut),
+                    })
+                    .unwrap();
+                    let res = store.set(KEY_COUNTER, &value);
+
+                    let mut flash = store.release();
+                    flash.operations_left = None;
+                    let mut store = KvStore::mount(flash).unwrap();
+                    let counter = get(&mut store, KEY_COUNTER).map(|(buf, len)| {
+                        assert_eq!(len, value.len());
+                        buf[0]
+                    });
+                    // either the old or the new value, never garbage
+                    let previous = step.checked_sub(1);
+                    match res {
+                        Ok(()) => assert_eq!(counter, Some(step)),
+                        Err(_) => assert!(counter == previous || counter == Some(step)),
+                    }
+                    assert_value(&mut store, KEY_SSID, Some(b"lab"));
+
+                    // the store stays usable
+                    store.set(KEY_COUNTER, &value).unwrap();
+                    assert_value(&mut store, KEY_SSID, Some(b"lab"));
+
+                    if res.is_ok() {
+                        break;
+                    }
+                }
+
+                let mut data = base;
+                {
+                    let mut flash = MemFlash::new(&mut data[..len], SECTOR_SIZE);
+                    flash.data().copy_from_slice(&base[..len]);
+                    let mut store = KvStore::mount(flash).unwrap();
+                    store.set(KEY_COUNTER, &value).unwrap();
+                }
+                base = data;
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Persistent settings in a reserved flash region.
+//!
+//! ```ignore
+//! let mut store = KvStore::mount(XipFlash::reserved()).unwrap();
+//! store.set(KEY_DEVICE_NAME, b"sensor").unwrap();
+//! ```
+
+mod flash;
+pub use flash::{Flash, FlashError, MemFlash};
+
+mod kv;
+pub use kv::{KvError, KvStore, FORMAT_VERSION, KEY_MAX_LEN};
+
+mod xip;
+pub use xip::{XipFlash, FLASH_SECTOR_SIZE};
+
+pub const KEY_WIFI_SSID: &str = "wifi.ssid";
+pub const KEY_WIFI_PASSPHRASE: &str = "wifi.passphrase";
+/// Precomputed PSK, see `wifi::psk::derive_psk`
+pub const KEY_WIFI_PSK: &str = "wifi.psk";
+/// Four address bytes followed by the prefix length
+pub const KEY_IP_ADDRESS: &str = "ip.address";
+/// Four address bytes
+pub const KEY_IP_GATEWAY: &str = "ip.gateway";
+/// Four address bytes
+pub const KEY_IP_DNS: &str = "ip.dns";
+pub const KEY_DEVICE_NAME: &str = "device.name";
@@ -0,0 +1,138 @@
+use super::{Flash, FlashError};
+
+/// Erase sector size of the SPI flash
+pub const FLASH_SECTOR_SIZE: u32 = 4096;
+
+// the ROM driver exports a table of function pointers
+const ROM_API_BASE: usize = 0x2101_0800;
+const ROM_API_SFLASH_READ_WITH_LOCK: usize = 170;
+const ROM_API_SFLASH_WRITE_WITH_LOCK: usize = 171;
+const ROM_API_SFLASH_ERASE_WITH_LOCK: usize = 172;
+
+type SflashReadWrite =
+    unsafe extern "C" fn(cfg: *const u8, addr: u32, data: *mut u8, len: i32) -> i32;
+type SflashErase = unsafe extern "C" fn(cfg: *const u8, addr: u32, len: i32) -> i32;
+
+/// Size of the ROM driver's `SPI_Flash_Cfg_Type`
+const FLASH_CFG_LEN: usize = 84;
+/// "FCFG", precedes the flash configuration in the boot header
+const FLASH_CFG_MAGIC: u32 = 0x4746_4346;
+
+// word aligned, the ROM driver reads 16 bit fields from it
+static mut FLASH_CFG: Option<[u32; FLASH_CFG_LEN / 4]> = None;
+
+extern "C" {
+    // flash configuration of the boot header handed over by boot2
+    static __boot2_flash_cfg_src: u8;
+
+    // see memory.x
+    static __kvstore_flash_offset: u8;
+    static __kvstore_flash_size: u8;
+}
+
+/// A region of the SPI flash accessed via the ROM driver. The ROM functions
+/// switch off XIP while they run so interrupts are disabled meanwhile.
+pub struct XipFlash {
+    offset: u32,
+    size: u32,
+}
+
+impl XipFlash {
+    /// `offset` and `size` are in bytes from the start of the flash chip and
+    /// have to be multiples of `FLASH_SECTOR_SIZE`
+    pub fn new(offset: u32, size: u32) -> XipFlash {
+        assert!(offset % FLASH_SECTOR_SIZE == 0 && size % FLASH_SECTOR_SIZE == 0);
+        XipFlash { offset, size }
+    }
+
+    /// The region reserved by `__kvstore_flash_offset` and `__kvstore_flash_size` in memory.x
+    pub fn reserved() -> XipFlash {
+        unsafe {
+            XipFlash::new(
+                &__kvstore_flash_offset as *const u8 as u32,
+                &__kvstore_flash_size as *const u8 as u32,
+            )
+        }
+    }
+
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<u32, FlashError> {
+        match offset.checked_add(len as u32) {
+            Some(end) if end <= self.size => Ok(self.offset + offset),
+            _ => Err(FlashError::OutOfBounds),
+        }
+    }
+}
+
+impl Flash for XipFlash {
+    fn sector_size(&self) -> u32 {
+        FLASH_SECTOR_SIZE
+    }
+
+    fn sectors(&self) -> u32 {
+        self.size / FLASH_SECTOR_SIZE
+    }
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), FlashError> {
+        let addr = self.check_bounds(offset, buf.len())?;
+        let cfg = flash_cfg()?;
+        let res = riscv::interrupt::free(|_| unsafe {
+            let read: SflashReadWrite = rom_api(ROM_API_SFLASH_READ_WITH_LOCK);
+            read(cfg, addr, buf.as_mut_ptr(), buf.len() as i32)
+        });
+        result(res)
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), FlashError> {
+        let addr = self.check_bounds(offset, data.len())?;
+        let cfg = flash_cfg()?;
+        let res = riscv::interrupt::free(|_| unsafe {
+            let write: SflashReadWrite = rom_api(ROM_API_SFLASH_WRITE_WITH_LOCK);
+            write(cfg, addr, data.as_ptr() as *mut u8, data.len() as i32)
+        });
+        result(res)
+    }
+
+    fn erase(&mut self, sector: u32) -> Result<(), FlashError> {
+        let addr = self.check_bounds(sector * FLASH_SECTOR_SIZE, FLASH_SECTOR_SIZE as usize)?;
+        let cfg = flash_cfg()?;
+        let res = riscv::interrupt::free(|_| unsafe {
+            let erase: SflashErase = rom_api(ROM_API_SFLASH_ERASE_WITH_LOCK);
+            erase(cfg, addr, FLASH_SECTOR_SIZE as i32)
+        });
+        result(res)
+    }
+}
+
+unsafe fn rom_api<T: Copy>(index: usize) -> T {
+    let entry = (ROM_API_BASE + index * 4) as *const usize;
+    core::mem::transmute_copy(&entry.read_volatile())
+}
+
+/// The flash configuration, copied on first use like `bl_flash_init` of the
+/// SDK does. boot2 leaves the magic followed by the `SPI_Flash_Cfg_Type` of the
+/// boot header at `__boot2_flash_cfg_src`, memory.x keeps that RAM unused.
+fn flash_cfg() -> Result<*const u8, FlashError> {
+    riscv::interrupt::free(|_| unsafe {
+        if FLASH_CFG.is_none() {
+            let src = &__boot2_flash_cfg_src as *const u8 as *const u32;
+            if src.read_volatile() != FLASH_CFG_MAGIC {
+                return Err(FlashError::NoConfig);
+            }
+
+            let mut cfg = [0u32; FLASH_CFG_LEN / 4];
+            for (index, word) in cfg.iter_mut().enumerate() {
+                *word = src.add(1 + index).read_volatile();
+            }
+            FLASH_CFG = Some(cfg);
+        }
+
+        Ok(FLASH_CFG.as_ref().unwrap().as_ptr() as *const u8)
+    })
+}
+
+fn result(res: i32) -> Result<(), FlashError> {
+    match res {
+        0 => Ok(()),
+        err => Err(FlashError::Failed(err)),
+    }
+}
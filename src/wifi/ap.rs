@@ -0,0 +1,184 @@
+use crate::binary::c_types::c_void;
+use crate::binary::wifi_mgmr;
+
+use super::connect::{PASSPHRASE_MAX_LEN, PASSPHRASE_MIN_LEN, SSID_MAX_LEN};
+use super::regulatory_domain;
+
+/// Maximum number of stations the blob can keep track of
+pub const AP_MAX_STATIONS: usize = 10;
+
+static mut AP_INTERFACE: wifi_mgmr::wifi_interface_t = core::ptr::null_mut();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApError {
+    /// The SSID is empty or longer than 32 bytes
+    InvalidSsid,
+    /// The passphrase is neither empty nor 8 to 63 bytes long
+    InvalidPassphrase,
+    /// The channel isn't allowed in the regulatory domain
+    InvalidChannel,
+    InvalidMaxStations,
+    AlreadyStarted,
+    NotStarted,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StationInfo {
+    /// Index used to identify the station e.g. for `kick_station`
+    pub sta_idx: u8,
+    pub mac: [u8; 6],
+    pub rssi: i32,
+    pub data_rate: u8,
+}
+
+/// Starts an access point. Use an empty passphrase for an open network.
+pub fn start_ap(
+    ssid: &str,
+    passphrase: &str,
+    channel: u8,
+    hidden: bool,
+    max_sta: u8,
+) -> Result<(), ApError> {
+    if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+        return Err(ApError::InvalidSsid);
+    }
+
+    if !passphrase.is_empty()
+        && (passphrase.len() < PASSPHRASE_MIN_LEN || passphrase.len() > PASSPHRASE_MAX_LEN)
+    {
+        return Err(ApError::InvalidPassphrase);
+    }
+
+    if !regulatory_domain().allows_channel(channel) {
+        return Err(ApError::InvalidChannel);
+    }
+
+    if max_sta < 1 || max_sta as usize > AP_MAX_STATIONS {
+        return Err(ApError::InvalidMaxStations);
+    }
+
+    if is_ap_started() {
+        return Err(ApError::AlreadyStarted);
+    }
+
+    let mut ssid_buf = [0u8; SSID_MAX_LEN + 1];
+    let mut passphrase_buf = [0u8; PASSPHRASE_MAX_LEN + 1];
+    ssid_buf[..ssid.len()].copy_from_slice(ssid.as_bytes());
+    passphrase_buf[..passphrase.len()].copy_from_slice(passphrase.as_bytes());
+
+    let passphrase_ptr = if passphrase.is_empty() {
+        core::ptr::null_mut()
+    } else {
+        &mut passphrase_buf as *mut u8
+    };
+
+    unsafe {
+        let interface = wifi_mgmr::wifi_mgmr_ap_enable(core::ptr::null_mut::<c_void>());
+        riscv::interrupt::free(|_| {
+            AP_INTERFACE = interface;
+        });
+
+        wifi_mgmr::wifi_mgmr_conf_max_sta(max_sta);
+
+        let res = wifi_mgmr::wifi_mgmr_ap_start(
+            &mut AP_INTERFACE as *mut _,
+            &mut ssid_buf as *mut u8,
+            hidden as i32,
+            passphrase_ptr,
+            channel as i32,
+        );
+
+        if res != 0 {
+            riscv::interrupt::free(|_| {
+                AP_INTERFACE = core::ptr::null_mut();
+            });
+            return Err(ApError::Failed(res));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn stop_ap() -> Result<(), ApError> {
+    if !is_ap_started() {
+        return Err(ApError::NotStarted);
+    }
+
+    let res = unsafe { wifi_mgmr::wifi_mgmr_ap_stop(&mut AP_INTERFACE as *mut _) };
+
+    riscv::interrupt::free(|_| unsafe {
+        AP_INTERFACE = core::ptr::null_mut();
+    });
+
+    if res != 0 {
+        return Err(ApError::Failed(res));
+    }
+
+    Ok(())
+}
+
+pub fn is_ap_started() -> bool {
+    riscv::interrupt::free(|_| unsafe { !AP_INTERFACE.is_null() })
+}
+
+/// Lists the stations currently connected to our access point
+pub fn ap_stations() -> [Option<StationInfo>; AP_MAX_STATIONS] {
+    let mut result = [None; AP_MAX_STATIONS];
+
+    if !is_ap_started() {
+        return result;
+    }
+
+    let mut sta_cnt = 0u8;
+    unsafe {
+        wifi_mgmr::wifi_mgmr_ap_sta_cnt_get(&mut sta_cnt);
+    }
+
+    let mut next = 0;
+    for idx in 0..sta_cnt.min(AP_MAX_STATIONS as u8) {
+        let mut info = wifi_mgmr::wifi_sta_basic_info {
+            sta_idx: 0,
+            is_used: 0,
+            sta_mac: [0u8; 6],
+            tsfhi: 0,
+            tsflo: 0,
+            rssi: 0,
+            data_rate: 0,
+        };
+
+        unsafe {
+            wifi_mgmr::wifi_mgmr_ap_sta_info_get(&mut info, idx);
+        }
+
+        // 0xef marks an invalid station index
+        if info.is_used == 0 || info.sta_idx == 0xef {
+            continue;
+        }
+
+        result[next] = Some(StationInfo {
+            sta_idx: info.sta_idx,
+            mac: info.sta_mac,
+            rssi: info.rssi,
+            data_rate: info.data_rate,
+        });
+        next += 1;
+    }
+
+    result
+}
+
+/// Disconnects a station from our access point
+pub fn kick_station(sta_idx: u8) -> Result<(), ApError> {
+    if !is_ap_started() {
+        return Err(ApError::NotStarted);
+    }
+
+    let res = unsafe { wifi_mgmr::wifi_mgmr_ap_sta_delete(sta_idx) };
+    if res != 0 {
+        return Err(ApError::Failed(res));
+    }
+
+    Ok(())
+}
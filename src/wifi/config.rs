@@ -0,0 +1,139 @@
+use embedded_time::duration::Milliseconds;
+
+use super::{
+    power, validate_custom_mac, CountryError, MacError, MacSource, PowerSave, PowerSaveError,
+    EFUSE_MAC_SLOTS,
+};
+
+/// Longest hostname the blob can store
+pub const HOSTNAME_MAX_LEN: usize = 31;
+
+/// Settings used by `wifi_init`
+///
+/// ```ignore
+/// wifi_init(WifiConfig::new().country("DE").hostname("sensor")).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct WifiConfig<'a> {
+    pub(crate) mac_source: MacSource,
+    pub(crate) hostname: Option<&'a str>,
+    pub(crate) country: &'a str,
+    pub(crate) autoconnect: bool,
+    pub(crate) power_save: Option<PowerSave>,
+    pub(crate) beacon_interval: Option<u16>,
+    pub(crate) init_timeout: Milliseconds,
+}
+
+impl<'a> Default for WifiConfig<'a> {
+    fn default() -> Self {
+        WifiConfig {
+            mac_source: MacSource::default(),
+            hostname: None,
+            country: "CN",
+            autoconnect: false,
+            power_save: None,
+            beacon_interval: None,
+            init_timeout: Milliseconds(5_000),
+        }
+    }
+}
+
+impl<'a> WifiConfig<'a> {
+    pub fn new() -> WifiConfig<'a> {
+        WifiConfig::default()
+    }
+
+    /// Locally administered station MAC instead of the one from efuse,
+    /// same as `mac_source(MacSource::Custom(mac))`
+    pub fn mac(self, mac: [u8; 6]) -> Self {
+        self.mac_source(MacSource::Custom(mac))
+    }
+
+    /// Where to take the station MAC from, the AP MAC is derived from it
+    pub fn mac_source(mut self, mac_source: MacSource) -> Self {
+        self.mac_source = mac_source;
+        self
+    }
+
+    pub fn hostname(mut self, hostname: &'a str) -> Self {
+        self.hostname = Some(hostname);
+        self
+    }
+
+    /// ISO 3166 country code selecting the regulatory domain, "CN" by default
+    pub fn country(mut self, country_code: &'a str) -> Self {
+        self.country = country_code;
+        self
+    }
+
+    /// Let the blob reconnect on its own after the connection got lost
+    pub fn autoconnect(mut self, autoconnect: bool) -> Self {
+        self.autoconnect = autoconnect;
+        self
+    }
+
+    /// Power save to configure, it's left alone if not given
+    pub fn power_save(mut self, power_save: PowerSave) -> Self {
+        self.power_save = Some(power_save);
+        self
+    }
+
+    /// Beacon interval of our AP in TU (1.024 ms)
+    pub fn beacon_interval(mut self, beacon_interval: u16) -> Self {
+        self.beacon_interval = Some(beacon_interval);
+        self
+    }
+
+    /// How long to wait for the blob to come up
+    pub fn init_timeout(mut self, timeout: Milliseconds) -> Self {
+        self.init_timeout = timeout;
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), WifiInitError> {
+        match self.mac_source {
+            MacSource::Custom(mac) => validate_custom_mac(&mac).map_err(WifiInitError::Mac)?,
+            MacSource::EfuseSlot(slot) if slot >= EFUSE_MAC_SLOTS => {
+                return Err(WifiInitError::Mac(MacError::InvalidSlot))
+            }
+            _ => (),
+        }
+
+        if let Some(hostname) = self.hostname {
+            if hostname.is_empty()
+                || hostname.len() > HOSTNAME_MAX_LEN
+                || hostname.bytes().any(|b| b == 0)
+            {
+                return Err(WifiInitError::InvalidHostname);
+            }
+        }
+
+        if self.beacon_interval == Some(0) {
+            return Err(WifiInitError::InvalidBeaconInterval);
+        }
+
+        if let Some(power_save) = &self.power_save {
+            power::validate(power_save).map_err(WifiInitError::PowerSave)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WifiInitError {
+    /// The MAC source didn't provide a valid MAC
+    Mac(MacError),
+    /// The hostname is empty, longer than 31 bytes or contains a NUL
+    InvalidHostname,
+    /// Beacon interval 0 isn't allowed
+    InvalidBeaconInterval,
+    Country(CountryError),
+    PowerSave(PowerSaveError),
+    /// The blob didn't signal it's ready within `init_timeout`
+    Timeout,
+    /// `wifi_init` was called before, the blob can't be started twice
+    AlreadyInitialized,
+    /// The blob returned an error
+    Failed(i32),
+}
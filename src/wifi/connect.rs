@@ -0,0 +1,384 @@
+use embedded_time::duration::Milliseconds;
+
+use crate::binary::{wifi_mgmr, wifi_mgmr_api};
+use crate::compat::get_time;
+
+use super::psk::{psk_from_hex, psk_to_hex, PSK_LEN};
+use super::{regulatory_domain, WifiEvent};
+
+pub const SSID_MAX_LEN: usize = 32;
+pub const PASSPHRASE_MIN_LEN: usize = 8;
+pub const PASSPHRASE_MAX_LEN: usize = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConnectState {
+    Idle,
+    Connecting,
+    Connected,
+    Failed,
+}
+
+static mut CONNECT_STATE: ConnectState = ConnectState::Idle;
+// counts connection attempts so a handle only cancels its own attempt
+static mut ATTEMPT: u32 = 0;
+// set once the blob reports it's connecting for the current attempt, disconnects
+// before that belong to the previous link
+static mut ASSOCIATING: bool = false;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectError {
+    /// The SSID is empty or longer than 32 bytes
+    InvalidSsid,
+    /// The passphrase is neither empty nor 8 to 63 bytes long
+    InvalidPassphrase,
+    /// The PSK isn't 64 hex digits
+    InvalidPsk,
+    /// The channel isn't allowed in the regulatory domain
+    InvalidChannel,
+    /// A connection attempt is already in progress
+    Busy,
+    /// The blob refused to start connecting
+    Rejected(i32),
+    /// Not connected within the given timeout
+    Timeout,
+    /// The AP refused the connection or it got lost while connecting
+    Failed { status_code: u16, reason_code: u16 },
+}
+
+impl ConnectError {
+    /// Human readable description of the blob's status code
+    pub fn status_str(&self) -> Option<&'static str> {
+        match self {
+            ConnectError::Failed { status_code, .. } => Some(status_code_str(*status_code)),
+            _ => None,
+        }
+    }
+}
+
+/// A connection attempt started by [connect_async]
+/// Dropping the handle while connecting cancels the attempt.
+pub struct ConnectHandle {
+    attempt: u32,
+    started: Milliseconds,
+    timeout: Option<Milliseconds>,
+}
+
+impl ConnectHandle {
+    /// Returns `WouldBlock` as long as the connection attempt is ongoing
+    pub fn poll(&mut self) -> nb::Result<(), ConnectError> {
+        let state = riscv::interrupt::free(|_| unsafe { CONNECT_STATE });
+
+        match state {
+            ConnectState::Connected => Ok(()),
+            ConnectState::Failed | ConnectState::Idle => {
+                let (status_code, reason_code) = conn_result();
+                Err(nb::Error::Other(ConnectError::Failed {
+                    status_code,
+                    reason_code,
+                }))
+            }
+            ConnectState::Connecting => {
+                if let Some(timeout) = self.timeout {
+                    if get_time().0.wrapping_sub(self.started.0) >= timeout.0 {
+                        unsafe {
+                            wifi_mgmr::wifi_mgmr_sta_disconnect();
+                        }
+                        set_state(ConnectState::Idle);
+                        return Err(nb::Error::Other(ConnectError::Timeout));
+                    }
+                }
+
+                Err(nb::Error::WouldBlock)
+            }
+        }
+    }
+}
+
+impl Drop for ConnectHandle {
+    fn drop(&mut self) {
+        let pending = riscv::interrupt::free(|_| unsafe {
+            if CONNECT_STATE == ConnectState::Connecting && ATTEMPT == self.attempt {
+                CONNECT_STATE = ConnectState::Idle;
+                true
+            } else {
+                false
+            }
+        });
+        if pending {
+            unsafe {
+                wifi_mgmr::wifi_mgmr_sta_disconnect();
+            }
+        }
+    }
+}
+
+/// Options for [connect_with_options]
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectOptions {
+    /// Connect to this AP instead of the strongest one with the SSID
+    pub bssid: Option<[u8; 6]>,
+    /// Channel of the AP, saves scanning all channels
+    pub channel: Option<u8>,
+    /// PSK derived from passphrase and SSID, saves the expensive derivation.
+    /// The passphrase may be empty if this is given.
+    pub psk: Option<[u8; 32]>,
+    /// Number of attempts using BSSID and channel before falling back to a full scan
+    pub time_to_live: i32,
+    /// Never fall back to a full scan
+    pub persistent: bool,
+    pub timeout: Option<Milliseconds>,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        ConnectOptions {
+            bssid: None,
+            channel: None,
+            psk: None,
+            time_to_live: 1,
+            persistent: false,
+            timeout: None,
+        }
+    }
+}
+
+/// Connects to the given AP and waits until we got an IP address.
+/// Use an empty passphrase for open networks.
+pub fn connect(
+    ssid: &str,
+    passphrase: &str,
+    timeout: Option<Milliseconds>,
+) -> Result<(), ConnectError> {
+    let mut handle = connect_async(ssid, passphrase, timeout)?;
+    nb::block!(handle.poll())
+}
+
+/// Starts connecting to the given AP. The returned handle can be polled
+/// e.g. from the network loop.
+pub fn connect_async(
+    ssid: &str,
+    passphrase: &str,
+    timeout: Option<Milliseconds>,
+) -> Result<ConnectHandle, ConnectError> {
+    connect_with_options_async(
+        ssid,
+        passphrase,
+        &ConnectOptions {
+            timeout,
+            ..ConnectOptions::default()
+        },
+    )
+}
+
+/// Connects using a PSK given as 64 hex digits instead of a passphrase,
+/// see [super::psk::derive_psk]
+pub fn connect_with_psk(
+    ssid: &str,
+    psk_hex: &str,
+    timeout: Option<Milliseconds>,
+) -> Result<(), ConnectError> {
+    let psk = psk_from_hex(psk_hex).map_err(|_| ConnectError::InvalidPsk)?;
+
+    connect_with_options(
+        ssid,
+        "",
+        &ConnectOptions {
+            psk: Some(psk),
+            timeout,
+            ..ConnectOptions::default()
+        },
+    )
+}
+
+/// Like [connect] but e.g. pins the BSSID and channel to reconnect faster
+pub fn connect_with_options(
+    ssid: &str,
+    passphrase: &str,
+    options: &ConnectOptions,
+) -> Result<(), ConnectError> {
+    let mut handle = connect_with_options_async(ssid, passphrase, options)?;
+    nb::block!(handle.poll())
+}
+
+pub fn connect_with_options_async(
+    ssid: &str,
+    passphrase: &str,
+    options: &ConnectOptions,
+) -> Result<ConnectHandle, ConnectError> {
+    let mut ssid_buf = [0u8; SSID_MAX_LEN + 1];
+    let mut passphrase_buf = [0u8; PASSPHRASE_MAX_LEN + 1];
+    let mut psk_buf = [0u8; PSK_LEN * 2 + 1];
+
+    if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+        return Err(ConnectError::InvalidSsid);
+    }
+
+    if !passphrase.is_empty()
+        && (passphrase.len() < PASSPHRASE_MIN_LEN || passphrase.len() > PASSPHRASE_MAX_LEN)
+    {
+        return Err(ConnectError::InvalidPassphrase);
+    }
+
+    if let Some(channel) = options.channel {
+        if !regulatory_domain().allows_channel(channel) {
+            return Err(ConnectError::InvalidChannel);
+        }
+    }
+
+    ssid_buf[..ssid.len()].copy_from_slice(ssid.as_bytes());
+    passphrase_buf[..passphrase.len()].copy_from_slice(passphrase.as_bytes());
+
+    let attempt = riscv::interrupt::free(|_| unsafe {
+        if CONNECT_STATE == ConnectState::Connecting {
+            None
+        } else {
+            CONNECT_STATE = ConnectState::Connecting;
+            ATTEMPT = ATTEMPT.wrapping_add(1);
+            ASSOCIATING = false;
+            super::STA_DOWN_REQUESTED = false;
+            Some(ATTEMPT)
+        }
+    });
+    let attempt = attempt.ok_or(ConnectError::Busy)?;
+
+    let mut bssid = options.bssid.unwrap_or_default();
+    let mut ext_param = wifi_mgmr_api::ap_connect_adv_t {
+        psk: core::ptr::null_mut(),
+        ap_info: wifi_mgmr_api::ap_info {
+            type_: if options.persistent {
+                wifi_mgmr_api::ap_info_type_AP_INFO_TYPE_PRESIST
+            } else {
+                wifi_mgmr_api::ap_info_type_AP_INFO_TYPE_SUGGEST
+            },
+            time_to_live: options.time_to_live,
+            bssid: core::ptr::null_mut(),
+            band: 0,
+            freq: 0,
+        },
+    };
+
+    if options.bssid.is_some() {
+        ext_param.ap_info.bssid = &mut bssid as *mut _;
+    }
+
+    if let Some(channel) = options.channel {
+        ext_param.ap_info.freq = channel_to_freq(channel);
+    }
+
+    if let Some(psk) = options.psk {
+        psk_buf[..PSK_LEN * 2].copy_from_slice(&psk_to_hex(&psk));
+        ext_param.psk = &mut psk_buf as *mut _;
+        unsafe {
+            wifi_mgmr_api::wifi_mgmr_sta_psk_set(&mut psk_buf as *mut _);
+        }
+    }
+
+    let use_ext_param =
+        options.bssid.is_some() || options.channel.is_some() || options.psk.is_some();
+    let ext_param_ptr = if use_ext_param {
+        &ext_param as *const _
+    } else {
+        core::ptr::null()
+    };
+
+    let res = unsafe {
+        wifi_mgmr_api::wifi_mgmr_api_connect(
+            &mut ssid_buf as *mut _,
+            &mut passphrase_buf as *mut _,
+            ext_param_ptr,
+        )
+    };
+
+    if res != 0 {
+        set_state(ConnectState::Idle);
+        return Err(ConnectError::Rejected(res));
+    }
+
+    Ok(ConnectHandle {
+        attempt,
+        started: get_time(),
+        timeout: options.timeout,
+    })
+}
+
+/// Last status and reason code reported by the blob for a connection attempt
+pub fn conn_result() -> (u16, u16) {
+    let mut status_code = 0u16;
+    let mut reason_code = 0u16;
+    unsafe {
+        wifi_mgmr::wifi_mgmr_conn_result_get(&mut status_code, &mut reason_code);
+    }
+    (status_code, reason_code)
+}
+
+pub fn status_code_str(status_code: u16) -> &'static str {
+    unsafe { super::str_from_c(wifi_mgmr::wifi_mgmr_status_code_str(status_code)) }
+}
+
+/// Center frequency in MHz of a 2.4 GHz channel, 0 if unknown
+pub fn channel_to_freq(channel: u8) -> u16 {
+    match channel {
+        1..=13 => 2407 + 5 * channel as u16,
+        14 => 2484,
+        _ => 0,
+    }
+}
+
+fn set_state(state: ConnectState) {
+    riscv::interrupt::free(|_| unsafe {
+        CONNECT_STATE = state;
+    });
+}
+
+/// Forgets about a connection attempt or established connection
+pub(crate) fn reset_state() {
+    set_state(ConnectState::Idle);
+}
+
+/// Must be called with interrupts disabled.
+pub(crate) unsafe fn on_event(event: WifiEvent) {
+    if event == WifiEvent::Connecting && CONNECT_STATE == ConnectState::Connecting {
+        ASSOCIATING = true;
+    } else if event == WifiEvent::GotIp {
+        CONNECT_STATE = ConnectState::Connected;
+    } else if event.is_disconnect() {
+        CONNECT_STATE = match CONNECT_STATE {
+            ConnectState::Connecting if ASSOCIATING => ConnectState::Failed,
+            // the previous link went down, the attempt is still ongoing
+            ConnectState::Connecting => ConnectState::Connecting,
+            _ => ConnectState::Idle,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_frequencies() {
+        assert_eq!(channel_to_freq(1), 2412);
+        assert_eq!(channel_to_freq(6), 2437);
+        assert_eq!(channel_to_freq(13), 2472);
+        assert_eq!(channel_to_freq(14), 2484);
+        assert_eq!(channel_to_freq(0), 0);
+        assert_eq!(channel_to_freq(15), 0);
+    }
+
+    #[test]
+    fn late_disconnect_keeps_attempt() {
+        unsafe {
+            CONNECT_STATE = ConnectState::Connecting;
+            ASSOCIATING = false;
+
+            on_event(WifiEvent::Disconnected { status_code: 0 });
+            let state = CONNECT_STATE;
+            assert_eq!(state, ConnectState::Connecting);
+
+            on_event(WifiEvent::Connecting);
+            on_event(WifiEvent::Disconnected { status_code: 0 });
+            let state = CONNECT_STATE;
+            assert_eq!(state, ConnectState::Failed);
+        }
+    }
+}
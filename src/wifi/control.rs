@@ -0,0 +1,90 @@
+use crate::binary::{wifi_mgmr, wifi_mgmr_api};
+
+use super::{
+    connect, ip, is_ap_started, power, stop_ap, AP_CLIENTS, AP_CLIENTS_STALE, AP_MAX_STATIONS,
+    CONNECTED, DATA_QUEUE_RX, DATA_QUEUE_RX_AP, STA_DOWN_REQUESTED, STA_LINK_UP, TX_BUFFER_IN_USE,
+    TX_QUEUED, TX_QUEUE_SIZE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlError {
+    /// The blob returned an error
+    Failed(i32),
+}
+
+/// Leaves the network the station is connected to.
+/// [super::ReconnectSupervisor] and [super::NetworkProfiles] pause until the next connect.
+pub fn disconnect() -> Result<(), ControlError> {
+    let res = unsafe { wifi_mgmr::wifi_mgmr_sta_disconnect() };
+    sta_down();
+
+    if res != 0 {
+        return Err(ControlError::Failed(res));
+    }
+
+    Ok(())
+}
+
+/// Disconnects and puts the station into idle, stopping scans and reconnects
+pub fn idle() -> Result<(), ControlError> {
+    let res = unsafe { wifi_mgmr_api::wifi_mgmr_api_idle() };
+    sta_down();
+
+    if res != 0 {
+        return Err(ControlError::Failed(res));
+    }
+
+    Ok(())
+}
+
+/// Stops the AP and the station and resets the wifi manager. Wifi can't be
+/// used afterwards until the chip is reset: the blob can't be started twice so
+/// `wifi_init` fails. Use `disconnect` and connect again to switch networks.
+pub fn deinit() -> Result<(), ControlError> {
+    if is_ap_started() {
+        stop_ap().ok();
+    }
+
+    let res = unsafe { wifi_mgmr::wifi_mgmr_sta_disable(core::ptr::null_mut()) };
+    unsafe {
+        wifi_mgmr::wifi_mgmr_reset();
+    }
+
+    sta_down();
+
+    riscv::interrupt::free(|_| unsafe {
+        DATA_QUEUE_RX_AP.flush();
+        AP_CLIENTS = [None; AP_MAX_STATIONS];
+        AP_CLIENTS_STALE = false;
+        // no tx-done arrives for frames in flight after the reset
+        TX_BUFFER_IN_USE = [false; TX_QUEUE_SIZE];
+        TX_QUEUED = false;
+    });
+
+    if res != 0 {
+        return Err(ControlError::Failed(res));
+    }
+
+    Ok(())
+}
+
+/// True after `disconnect`, `idle` or `deinit` until a connection attempt is started
+pub fn sta_down_requested() -> bool {
+    riscv::interrupt::free(|_| unsafe { STA_DOWN_REQUESTED })
+}
+
+/// Resets the station's state and drops received frames. TX buffers are
+/// owned by the blob once handed to `bl_output` and only come back via
+/// tx-done or `bl602_netdev_free_txbuf`.
+fn sta_down() {
+    connect::reset_state();
+    ip::reset_state();
+    power::reset_state();
+
+    riscv::interrupt::free(|_| unsafe {
+        CONNECTED = false;
+        STA_LINK_UP = false;
+        STA_DOWN_REQUESTED = true;
+        DATA_QUEUE_RX.flush();
+    });
+}
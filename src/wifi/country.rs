@@ -0,0 +1,176 @@
+use crate::binary::wifi_mgmr;
+
+const CHANNELS: [u8; 14] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+
+/// ISO 3166 codes of countries using the EU rules
+const EU_COUNTRIES: [&str; 32] = [
+    "AT", "BE", "BG", "HR", "CY", "CZ", "DK", "EE", "FI", "FR", "DE", "GR", "HU", "IE", "IT", "LV",
+    "LT", "LU", "MT", "NL", "PL", "PT", "RO", "SK", "SI", "ES", "SE", "GB", "CH", "NO", "IS", "LI",
+];
+
+/// The regulatory domains known to the blob
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegulatoryDomain {
+    /// Channels 1 - 13
+    Cn,
+    /// Channels 1 - 13
+    Eu,
+    /// Channels 1 - 14
+    Jp,
+    /// Channels 1 - 11
+    Us,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountryError {
+    /// No regulatory domain is known for the country code
+    UnknownCountry,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+static mut DOMAIN: RegulatoryDomain = RegulatoryDomain::Cn;
+
+impl RegulatoryDomain {
+    /// Looks up the domain of an ISO 3166 alpha-2 country code, "EU" is accepted, too
+    pub fn from_country_code(code: &str) -> Option<RegulatoryDomain> {
+        let mut upper = [0u8; 2];
+        if code.len() != 2 {
+            return None;
+        }
+        for (dst, src) in upper.iter_mut().zip(code.bytes()) {
+            *dst = src.to_ascii_uppercase();
+        }
+        let code = core::str::from_utf8(&upper).ok()?;
+
+        match code {
+            "CN" => Some(RegulatoryDomain::Cn),
+            "JP" => Some(RegulatoryDomain::Jp),
+            "US" | "CA" => Some(RegulatoryDomain::Us),
+            "EU" => Some(RegulatoryDomain::Eu),
+            _ if EU_COUNTRIES.contains(&code) => Some(RegulatoryDomain::Eu),
+            _ => None,
+        }
+    }
+
+    /// The code the blob uses for this domain
+    pub fn code(&self) -> [u8; 2] {
+        match self {
+            RegulatoryDomain::Cn => *b"CN",
+            RegulatoryDomain::Eu => *b"EU",
+            RegulatoryDomain::Jp => *b"JP",
+            RegulatoryDomain::Us => *b"US",
+        }
+    }
+
+    pub fn channels(&self) -> &'static [u8] {
+        let count = match self {
+            RegulatoryDomain::Cn | RegulatoryDomain::Eu => 13,
+            RegulatoryDomain::Jp => 14,
+            RegulatoryDomain::Us => 11,
+        };
+        &CHANNELS[..count]
+    }
+
+    pub fn allows_channel(&self, channel: u8) -> bool {
+        self.channels().contains(&channel)
+    }
+
+    pub(crate) fn wifi_conf(&self) -> wifi_mgmr::wifi_conf_t {
+        let code = self.code();
+        wifi_mgmr::wifi_conf_t {
+            country_code: [code[0], code[1], 0],
+            channel_nums: self.channels().len() as i32,
+        }
+    }
+}
+
+/// The regulatory domain currently in use
+pub fn regulatory_domain() -> RegulatoryDomain {
+    riscv::interrupt::free(|_| unsafe { DOMAIN })
+}
+
+/// Remembers the domain used to initialize the blob
+pub(crate) fn init_regulatory_domain(domain: RegulatoryDomain) {
+    riscv::interrupt::free(|_| unsafe {
+        DOMAIN = domain;
+    });
+}
+
+/// Changes the regulatory domain at runtime
+pub fn set_country_code(code: &str) -> Result<RegulatoryDomain, CountryError> {
+    let domain = RegulatoryDomain::from_country_code(code).ok_or(CountryError::UnknownCountry)?;
+
+    let code = domain.code();
+    let mut code_buf = [code[0], code[1], 0];
+    let res = unsafe { wifi_mgmr::wifi_mgmr_set_country_code(&mut code_buf as *mut u8) };
+    if res != 0 {
+        return Err(CountryError::Failed(res));
+    }
+
+    init_regulatory_domain(domain);
+    Ok(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_country_code() {
+        assert_eq!(
+            RegulatoryDomain::from_country_code("CN"),
+            Some(RegulatoryDomain::Cn)
+        );
+        assert_eq!(
+            RegulatoryDomain::from_country_code("jp"),
+            Some(RegulatoryDomain::Jp)
+        );
+        assert_eq!(
+            RegulatoryDomain::from_country_code("Ca"),
+            Some(RegulatoryDomain::Us)
+        );
+        assert_eq!(
+            RegulatoryDomain::from_country_code("EU"),
+            Some(RegulatoryDomain::Eu)
+        );
+        assert_eq!(
+            RegulatoryDomain::from_country_code("de"),
+            Some(RegulatoryDomain::Eu)
+        );
+        assert_eq!(
+            RegulatoryDomain::from_country_code("GB"),
+            Some(RegulatoryDomain::Eu)
+        );
+    }
+
+    #[test]
+    fn unknown_country_code() {
+        assert_eq!(RegulatoryDomain::from_country_code("XX"), None);
+        assert_eq!(RegulatoryDomain::from_country_code(""), None);
+        assert_eq!(RegulatoryDomain::from_country_code("D"), None);
+        assert_eq!(RegulatoryDomain::from_country_code("DEU"), None);
+        assert_eq!(RegulatoryDomain::from_country_code("ä"), None);
+    }
+
+    #[test]
+    fn channels() {
+        assert_eq!(RegulatoryDomain::Us.channels().len(), 11);
+        assert_eq!(RegulatoryDomain::Eu.channels().len(), 13);
+        assert_eq!(RegulatoryDomain::Jp.channels().len(), 14);
+
+        assert!(RegulatoryDomain::Us.allows_channel(11));
+        assert!(!RegulatoryDomain::Us.allows_channel(12));
+        assert!(RegulatoryDomain::Cn.allows_channel(13));
+        assert!(!RegulatoryDomain::Cn.allows_channel(14));
+        assert!(RegulatoryDomain::Jp.allows_channel(14));
+        assert!(!RegulatoryDomain::Jp.allows_channel(0));
+    }
+
+    #[test]
+    fn wifi_conf() {
+        let conf = RegulatoryDomain::Jp.wifi_conf();
+        assert_eq!(conf.country_code, *b"JP\0");
+        assert_eq!(conf.channel_nums, 14);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::binary::wifi_mgmr::{
+    _WIFI_EVENT_CODE_WIFI_CMD_RECONNECT, _WIFI_EVENT_CODE_WIFI_ON_AP_STARTED,
+    _WIFI_EVENT_CODE_WIFI_ON_AP_STA_ADD, _WIFI_EVENT_CODE_WIFI_ON_AP_STA_DEL,
+    _WIFI_EVENT_CODE_WIFI_ON_AP_STOPPED, _WIFI_EVENT_CODE_WIFI_ON_CONNECTED,
+    _WIFI_EVENT_CODE_WIFI_ON_CONNECTING, _WIFI_EVENT_CODE_WIFI_ON_DISCONNECT,
+    _WIFI_EVENT_CODE_WIFI_ON_GOT_IP, _WIFI_EVENT_CODE_WIFI_ON_INIT_DONE,
+    _WIFI_EVENT_CODE_WIFI_ON_MGMR_DONE, _WIFI_EVENT_CODE_WIFI_ON_PRE_GOT_IP,
+    _WIFI_EVENT_CODE_WIFI_ON_SCAN_DONE, _WIFI_EVENT_CODE_WIFI_ON_SCAN_DONE_ONJOIN,
+};
+
+const EVENT_QUEUE_SIZE: usize = 16;
+
+/// Status code reported on disconnect when the 4-way handshake failed
+pub const STATUS_CODE_PSK_FAILURE: u16 = 8;
+/// Status code reported on disconnect when no AP with the SSID was found
+pub const STATUS_CODE_NO_AP_FOUND: u16 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WifiEvent {
+    InitDone,
+    MgmrDone,
+    Reconnect,
+    Connecting,
+    Connected,
+    /// The connection was lost or a connection attempt failed
+    Disconnected {
+        status_code: u16,
+    },
+    /// A connection attempt failed because of a wrong passphrase
+    PskError,
+    /// A connection attempt failed because the AP wasn't found
+    NoApFound,
+    PreGotIp,
+    GotIp,
+    ScanDone,
+    ScanDoneOnJoin,
+    ApStarted,
+    ApStopped,
+    ApStationAdded {
+        sta_idx: u8,
+    },
+    ApStationRemoved {
+        sta_idx: u8,
+    },
+    Other {
+        code: i32,
+        value: u32,
+    },
+}
+
+impl WifiEvent {
+    pub fn from_raw(code: i32, value: u32) -> WifiEvent {
+        match code {
+            _WIFI_EVENT_CODE_WIFI_ON_INIT_DONE => WifiEvent::InitDone,
+            _WIFI_EVENT_CODE_WIFI_ON_MGMR_DONE => WifiEvent::MgmrDone,
+            _WIFI_EVENT_CODE_WIFI_CMD_RECONNECT => WifiEvent::Reconnect,
+            _WIFI_EVENT_CODE_WIFI_ON_CONNECTING => WifiEvent::Connecting,
+            _WIFI_EVENT_CODE_WIFI_ON_CONNECTED => WifiEvent::Connected,
+            _WIFI_EVENT_CODE_WIFI_ON_DISCONNECT => match value as u16 {
+                STATUS_CODE_PSK_FAILURE => WifiEvent::PskError,
+                STATUS_CODE_NO_AP_FOUND => WifiEvent::NoApFound,
+                status_code => WifiEvent::Disconnected { status_code },
+            },
+            _WIFI_EVENT_CODE_WIFI_ON_PRE_GOT_IP => WifiEvent::PreGotIp,
+            _WIFI_EVENT_CODE_WIFI_ON_GOT_IP => WifiEvent::GotIp,
+            _WIFI_EVENT_CODE_WIFI_ON_SCAN_DONE => WifiEvent::ScanDone,
+            _WIFI_EVENT_CODE_WIFI_ON_SCAN_DONE_ONJOIN => WifiEvent::ScanDoneOnJoin,
+            _WIFI_EVENT_CODE_WIFI_ON_AP_STARTED => WifiEvent::ApStarted,
+            _WIFI_EVENT_CODE_WIFI_ON_AP_STOPPED => WifiEvent::ApStopped,
+            _WIFI_EVENT_CODE_WIFI_ON_AP_STA_ADD => WifiEvent::ApStationAdded {
+                sta_idx: value as u8,
+            },
+            _WIFI_EVENT_CODE_WIFI_ON_AP_STA_DEL => WifiEvent::ApStationRemoved {
+                sta_idx: value as u8,
+            },
+            _ => WifiEvent::Other { code, value },
+        }
+    }
+
+    /// True for events which mean we are not (or no longer) connected
+    pub fn is_disconnect(&self) -> bool {
+        matches!(
+            self,
+            WifiEvent::Disconnected { .. } | WifiEvent::PskError | WifiEvent::NoApFound
+        )
+    }
+}
+
+/// Fixed size queue of events. If the application doesn't poll the oldest
+/// events get overwritten.
+/// All methods must be called with interrupts disabled.
+pub(crate) struct EventQueue {
+    events: [Option<WifiEvent>; EVENT_QUEUE_SIZE],
+    read_index: usize,
+    len: usize,
+}
+
+impl EventQueue {
+    pub const fn new() -> EventQueue {
+        EventQueue {
+            events: [None; EVENT_QUEUE_SIZE],
+            read_index: 0,
+            len: 0,
+        }
+    }
+
+    pub fn enqueue(&mut self, event: WifiEvent) {
+        if self.len == EVENT_QUEUE_SIZE {
+            self.dequeue();
+        }
+
+        let write_index = (self.read_index + self.len) % EVENT_QUEUE_SIZE;
+        self.events[write_index] = Some(event);
+        self.len += 1;
+    }
+
+    pub fn dequeue(&mut self) -> Option<WifiEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.read_index].take();
+        self.read_index = (self.read_index + 1) % EVENT_QUEUE_SIZE;
+        self.len -= 1;
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_raw() {
+        assert_eq!(
+            WifiEvent::from_raw(_WIFI_EVENT_CODE_WIFI_ON_GOT_IP, 0),
+            WifiEvent::GotIp
+        );
+        assert_eq!(
+            WifiEvent::from_raw(_WIFI_EVENT_CODE_WIFI_ON_AP_STA_ADD, 3),
+            WifiEvent::ApStationAdded { sta_idx: 3 }
+        );
+        assert_eq!(
+            WifiEvent::from_raw(_WIFI_EVENT_CODE_WIFI_ON_AP_STA_DEL, 3),
+            WifiEvent::ApStationRemoved { sta_idx: 3 }
+        );
+        assert_eq!(
+            WifiEvent::from_raw(-1, 42),
+            WifiEvent::Other {
+                code: -1,
+                value: 42
+            }
+        );
+    }
+
+    #[test]
+    fn disconnect_status_codes() {
+        let code = _WIFI_EVENT_CODE_WIFI_ON_DISCONNECT;
+        assert_eq!(
+            WifiEvent::from_raw(code, STATUS_CODE_PSK_FAILURE as u32),
+            WifiEvent::PskError
+        );
+        assert_eq!(
+            WifiEvent::from_raw(code, STATUS_CODE_NO_AP_FOUND as u32),
+            WifiEvent::NoApFound
+        );
+        assert_eq!(
+            WifiEvent::from_raw(code, 3),
+            WifiEvent::Disconnected { status_code: 3 }
+        );
+    }
+
+    #[test]
+    fn is_disconnect() {
+        assert!(WifiEvent::Disconnected { status_code: 3 }.is_disconnect());
+        assert!(WifiEvent::PskError.is_disconnect());
+        assert!(WifiEvent::NoApFound.is_disconnect());
+        assert!(!WifiEvent::Connected.is_disconnect());
+        assert!(!WifiEvent::GotIp.is_disconnect());
+        assert!(!WifiEvent::ApStopped.is_disconnect());
+    }
+
+    #[test]
+    fn queue_keeps_order() {
+        let mut queue = EventQueue::new();
+        assert_eq!(queue.dequeue(), None);
+
+        queue.enqueue(WifiEvent::Connecting);
+        queue.enqueue(WifiEvent::Connected);
+        assert_eq!(queue.dequeue(), Some(WifiEvent::Connecting));
+
+        queue.enqueue(WifiEvent::GotIp);
+        assert_eq!(queue.dequeue(), Some(WifiEvent::Connected));
+        assert_eq!(queue.dequeue(), Some(WifiEvent::GotIp));
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn queue_overwrites_oldest() {
+        let mut queue = EventQueue::new();
+        for sta_idx in 0..EVENT_QUEUE_SIZE as u8 + 3 {
+            queue.enqueue(WifiEvent::ApStationAdded { sta_idx });
+        }
+
+        for sta_idx in 3..EVENT_QUEUE_SIZE as u8 + 3 {
+            assert_eq!(queue.dequeue(), Some(WifiEvent::ApStationAdded { sta_idx }));
+        }
+        assert_eq!(queue.dequeue(), None);
+    }
+}
@@ -0,0 +1,621 @@
+//! Parsing of IEEE 802.11 MAC headers and building of management frames.
+//!
+//! This doesn't depend on the blob so it can be used on captured frames anywhere.
+
+pub const SUBTYPE_ASSOC_REQUEST: u8 = 0;
+pub const SUBTYPE_ASSOC_RESPONSE: u8 = 1;
+pub const SUBTYPE_REASSOC_REQUEST: u8 = 2;
+pub const SUBTYPE_REASSOC_RESPONSE: u8 = 3;
+pub const SUBTYPE_PROBE_REQUEST: u8 = 4;
+pub const SUBTYPE_PROBE_RESPONSE: u8 = 5;
+pub const SUBTYPE_BEACON: u8 = 8;
+pub const SUBTYPE_DISASSOC: u8 = 10;
+pub const SUBTYPE_AUTH: u8 = 11;
+pub const SUBTYPE_DEAUTH: u8 = 12;
+pub const SUBTYPE_ACTION: u8 = 13;
+
+const SUBTYPE_CTS: u8 = 12;
+const SUBTYPE_ACK: u8 = 13;
+/// Data subtypes with this bit set carry a QoS control field
+const SUBTYPE_QOS_BIT: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameType {
+    Management,
+    Control,
+    Data,
+    Extension,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameControl {
+    pub protocol_version: u8,
+    pub frame_type: FrameType,
+    pub subtype: u8,
+    pub to_ds: bool,
+    pub from_ds: bool,
+    pub more_fragments: bool,
+    pub retry: bool,
+    pub power_management: bool,
+    pub more_data: bool,
+    pub protected: bool,
+    pub order: bool,
+}
+
+impl FrameControl {
+    pub fn from_raw(raw: u16) -> FrameControl {
+        let frame_type = match (raw >> 2) & 0b11 {
+            0 => FrameType::Management,
+            1 => FrameType::Control,
+            2 => FrameType::Data,
+            _ => FrameType::Extension,
+        };
+
+        FrameControl {
+            protocol_version: (raw & 0b11) as u8,
+            frame_type,
+            subtype: ((raw >> 4) & 0b1111) as u8,
+            to_ds: raw & (1 << 8) != 0,
+            from_ds: raw & (1 << 9) != 0,
+            more_fragments: raw & (1 << 10) != 0,
+            retry: raw & (1 << 11) != 0,
+            power_management: raw & (1 << 12) != 0,
+            more_data: raw & (1 << 13) != 0,
+            protected: raw & (1 << 14) != 0,
+            order: raw & (1 << 15) != 0,
+        }
+    }
+
+    pub fn to_raw(self) -> u16 {
+        let frame_type = match self.frame_type {
+            FrameType::Management => 0,
+            FrameType::Control => 1,
+            FrameType::Data => 2,
+            FrameType::Extension => 3,
+        };
+
+        (self.protocol_version as u16 & 0b11)
+            | (frame_type << 2)
+            | ((self.subtype as u16 & 0b1111) << 4)
+            | ((self.to_ds as u16) << 8)
+            | ((self.from_ds as u16) << 9)
+            | ((self.more_fragments as u16) << 10)
+            | ((self.retry as u16) << 11)
+            | ((self.power_management as u16) << 12)
+            | ((self.more_data as u16) << 13)
+            | ((self.protected as u16) << 14)
+            | ((self.order as u16) << 15)
+    }
+
+    pub fn is_management(&self, subtype: u8) -> bool {
+        self.frame_type == FrameType::Management && self.subtype == subtype
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceControl {
+    pub fragment_number: u8,
+    pub sequence_number: u16,
+}
+
+impl SequenceControl {
+    pub fn from_raw(raw: u16) -> SequenceControl {
+        SequenceControl {
+            fragment_number: (raw & 0xf) as u8,
+            sequence_number: raw >> 4,
+        }
+    }
+}
+
+/// Typed view of the MAC header of a frame.
+/// Fields not present in the frame's type are `None`.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameHeader<'a> {
+    pub frame_control: FrameControl,
+    pub duration: u16,
+    pub addr1: [u8; 6],
+    pub addr2: Option<[u8; 6]>,
+    pub addr3: Option<[u8; 6]>,
+    pub sequence_control: Option<SequenceControl>,
+    pub addr4: Option<[u8; 6]>,
+    pub qos_control: Option<u16>,
+    /// Everything after the header (including the FCS if the capture has it)
+    pub body: &'a [u8],
+}
+
+impl<'a> FrameHeader<'a> {
+    /// Parses the header, `None` if the frame is too short for its type
+    pub fn parse(frame: &'a [u8]) -> Option<FrameHeader<'a>> {
+        let mut reader = Reader {
+            data: frame,
+            pos: 0,
+        };
+
+        let frame_control = FrameControl::from_raw(reader.u16()?);
+        let duration = reader.u16()?;
+        let addr1 = reader.addr()?;
+
+        let mut header = FrameHeader {
+            frame_control,
+            duration,
+            addr1,
+            addr2: None,
+            addr3: None,
+            sequence_control: None,
+            addr4: None,
+            qos_control: None,
+            body: &[],
+        };
+
+        match frame_control.frame_type {
+            FrameType::Control => {
+                if frame_control.subtype != SUBTYPE_CTS && frame_control.subtype != SUBTYPE_ACK {
+                    header.addr2 = Some(reader.addr()?);
+                }
+            }
+            FrameType::Management | FrameType::Data => {
+                header.addr2 = Some(reader.addr()?);
+                header.addr3 = Some(reader.addr()?);
+                header.sequence_control = Some(SequenceControl::from_raw(reader.u16()?));
+
+                if frame_control.frame_type == FrameType::Data {
+                    if frame_control.to_ds && frame_control.from_ds {
+                        header.addr4 = Some(reader.addr()?);
+                    }
+
+                    if frame_control.subtype & SUBTYPE_QOS_BIT != 0 {
+                        header.qos_control = Some(reader.u16()?);
+                    }
+                }
+            }
+            FrameType::Extension => (),
+        }
+
+        header.body = &frame[reader.pos..];
+        Some(header)
+    }
+
+    /// Receiver address
+    pub fn receiver(&self) -> [u8; 6] {
+        self.addr1
+    }
+
+    /// Transmitter address, not present in ACK and CTS frames
+    pub fn transmitter(&self) -> Option<[u8; 6]> {
+        self.addr2
+    }
+
+    /// BSSID for management frames and data frames not using the DS
+    pub fn bssid(&self) -> Option<[u8; 6]> {
+        let fc = &self.frame_control;
+        match (fc.frame_type, fc.to_ds, fc.from_ds) {
+            (FrameType::Management, _, _) | (FrameType::Data, false, false) => self.addr3,
+            (FrameType::Data, true, false) => Some(self.addr1),
+            (FrameType::Data, false, true) => self.addr2,
+            _ => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.data.get(self.pos..self.pos + 2)?;
+        self.pos += 2;
+        Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn addr(&mut self) -> Option<[u8; 6]> {
+        let bytes = self.data.get(self.pos..self.pos + 6)?;
+        self.pos += 6;
+        let mut addr = [0u8; 6];
+        addr.copy_from_slice(bytes);
+        Some(addr)
+    }
+}
+
+pub const ELEMENT_SSID: u8 = 0;
+pub const ELEMENT_SUPPORTED_RATES: u8 = 1;
+pub const ELEMENT_DS_PARAMETER_SET: u8 = 3;
+pub const ELEMENT_VENDOR_SPECIFIC: u8 = 221;
+
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// Length of the header of management frames
+pub const MANAGEMENT_HEADER_LEN: usize = 24;
+
+/// 1, 2, 5.5 and 11 Mbit/s as basic rates
+const SUPPORTED_RATES: [u8; 4] = [0x82, 0x84, 0x8b, 0x96];
+const CAPABILITY_ESS: u16 = 0x0001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameError {
+    /// The frame doesn't fit into the buffer
+    BufferTooSmall,
+    /// The SSID is longer than 32 bytes
+    SsidTooLong,
+    /// Elements can hold at most 255 bytes
+    ElementTooLong,
+    InvalidChannel,
+}
+
+/// Writes a management frame into a buffer.
+///
+/// Start with one of the frame constructors and append elements as needed, e.g.
+/// `FrameWriter::beacon(&mut buf, mac, "ssid", 6, 100)?.vendor_element(oui, &data)?.len()`
+pub struct FrameWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> FrameWriter<'a> {
+    /// Writes the management header with the given subtype and addresses
+    pub fn management(
+        buf: &'a mut [u8],
+        subtype: u8,
+        da: [u8; 6],
+        sa: [u8; 6],
+        bssid: [u8; 6],
+    ) -> Result<FrameWriter<'a>, FrameError> {
+        let frame_control = FrameControl {
+            protocol_version: 0,
+            frame_type: FrameType::Management,
+            subtype,
+            to_ds: false,
+            from_ds: false,
+            more_fragments: false,
+            retry: false,
+            power_management: false,
+            more_data: false,
+            protected: false,
+            order: false,
+        };
+
+        let mut writer = FrameWriter { buf, len: 0 };
+        writer.u16(frame_control.to_raw())?;
+        writer.u16(0)?; // duration
+        writer.bytes(&da)?;
+        writer.bytes(&sa)?;
+        writer.bytes(&bssid)?;
+        writer.u16(0)?; // sequence control, filled in by the hardware
+        Ok(writer)
+    }
+
+    /// Beacon with SSID, supported rates and DS parameter set elements.
+    /// The beacon interval is in TU (1.024 ms).
+    pub fn beacon(
+        buf: &'a mut [u8],
+        bssid: [u8; 6],
+        ssid: &str,
+        channel: u8,
+        beacon_interval: u16,
+    ) -> Result<FrameWriter<'a>, FrameError> {
+        if !(1..=14).contains(&channel) {
+            return Err(FrameError::InvalidChannel);
+        }
+
+        let mut writer = FrameWriter::management(buf, SUBTYPE_BEACON, BROADCAST, bssid, bssid)?;
+        writer.bytes(&[0u8; 8])?; // timestamp, filled in by the hardware
+        writer.u16(beacon_interval)?;
+        writer.u16(CAPABILITY_ESS)?;
+        writer.ssid(ssid)?;
+        writer.element(ELEMENT_SUPPORTED_RATES, &SUPPORTED_RATES)?;
+        writer.element(ELEMENT_DS_PARAMETER_SET, &[channel])?;
+        Ok(writer)
+    }
+
+    /// Broadcast probe request, an empty SSID asks all APs to respond
+    pub fn probe_request(
+        buf: &'a mut [u8],
+        sa: [u8; 6],
+        ssid: &str,
+    ) -> Result<FrameWriter<'a>, FrameError> {
+        let mut writer =
+            FrameWriter::management(buf, SUBTYPE_PROBE_REQUEST, BROADCAST, sa, BROADCAST)?;
+        writer.ssid(ssid)?;
+        writer.element(ELEMENT_SUPPORTED_RATES, &SUPPORTED_RATES)?;
+        Ok(writer)
+    }
+
+    /// Action frame with the given category followed by the payload
+    pub fn action(
+        buf: &'a mut [u8],
+        da: [u8; 6],
+        sa: [u8; 6],
+        bssid: [u8; 6],
+        category: u8,
+        payload: &[u8],
+    ) -> Result<FrameWriter<'a>, FrameError> {
+        let mut writer = FrameWriter::management(buf, SUBTYPE_ACTION, da, sa, bssid)?;
+        writer.bytes(&[category])?;
+        writer.bytes(payload)?;
+        Ok(writer)
+    }
+
+    pub fn element(&mut self, id: u8, data: &[u8]) -> Result<&mut Self, FrameError> {
+        if data.len() > 255 {
+            return Err(FrameError::ElementTooLong);
+        }
+
+        self.bytes(&[id, data.len() as u8])?;
+        self.bytes(data)?;
+        Ok(self)
+    }
+
+    /// Vendor specific element, `data` follows the OUI
+    pub fn vendor_element(&mut self, oui: [u8; 3], data: &[u8]) -> Result<&mut Self, FrameError> {
+        if data.len() > 255 - oui.len() {
+            return Err(FrameError::ElementTooLong);
+        }
+
+        self.bytes(&[ELEMENT_VENDOR_SPECIFIC, (oui.len() + data.len()) as u8])?;
+        self.bytes(&oui)?;
+        self.bytes(data)?;
+        Ok(self)
+    }
+
+    pub fn bytes(&mut self, data: &[u8]) -> Result<&mut Self, FrameError> {
+        let end = self.len + data.len();
+        if end > self.buf.len() {
+            return Err(FrameError::BufferTooSmall);
+        }
+
+        self.buf[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(self)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The frame written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn ssid(&mut self, ssid: &str) -> Result<&mut Self, FrameError> {
+        if ssid.len() > 32 {
+            return Err(FrameError::SsidTooLong);
+        }
+
+        self.element(ELEMENT_SSID, ssid.as_bytes())
+    }
+
+    fn u16(&mut self, value: u16) -> Result<&mut Self, FrameError> {
+        self.bytes(&value.to_le_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AP: [u8; 6] = [0x00, 0x1d, 0x7e, 0x4a, 0x6b, 0x10];
+    const STA: [u8; 6] = [0xc8, 0x43, 0x57, 0x12, 0x34, 0x56];
+    const HOST: [u8; 6] = [0x3c, 0x71, 0xbf, 0x0a, 0x0b, 0x0c];
+    const PEER: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+    // beacon of "test" on channel 6 as a monitor mode capture shows it, FCS included
+    const BEACON: [u8; 59] = [
+        0x80, 0x00, 0x00, 0x00, // frame control, duration
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // da
+        0x00, 0x1d, 0x7e, 0x4a, 0x6b, 0x10, // sa
+        0x00, 0x1d, 0x7e, 0x4a, 0x6b, 0x10, // bssid
+        0x10, 0x7a, // sequence 0x7a1, fragment 0
+        0x8f, 0x33, 0x1a, 0x05, 0x00, 0x00, 0x00, 0x00, // timestamp
+        0x64, 0x00, 0x11, 0x04, // beacon interval, capabilities
+        0x00, 0x04, b't', b'e', b's', b't', // ssid
+        0x01, 0x08, 0x82, 0x84, 0x8b, 0x96, 0x0c, 0x12, 0x18, 0x24, // rates
+        0x03, 0x01, 0x06, // ds parameter set
+        0x3e, 0x9a, 0x51, 0xd7, // fcs
+    ];
+
+    fn data_frame(flags: u8, addrs: &[[u8; 6]], qos: bool) -> ([u8; 64], usize) {
+        let mut frame = [0u8; 64];
+        frame[0] = if qos { 0x88 } else { 0x08 };
+        frame[1] = flags;
+        frame[2..4].copy_from_slice(&[0x2c, 0x00]);
+
+        let mut len = 4;
+        for (i, addr) in addrs.iter().enumerate() {
+            frame[len..len + 6].copy_from_slice(addr);
+            len += 6;
+            if i == 2 {
+                frame[len..len + 2].copy_from_slice(&[0x50, 0x3b]);
+                len += 2;
+            }
+        }
+        if qos {
+            frame[len..len + 2].copy_from_slice(&[0x06, 0x00]);
+            len += 2;
+        }
+
+        // LLC/SNAP of an IPv4 packet
+        frame[len..len + 8].copy_from_slice(&[0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00]);
+        (frame, len + 8)
+    }
+
+    #[test]
+    fn beacon() {
+        let header = FrameHeader::parse(&BEACON).unwrap();
+        let fc = header.frame_control;
+        assert_eq!(fc.frame_type, FrameType::Management);
+        assert!(fc.is_management(SUBTYPE_BEACON));
+        assert!(!fc.to_ds && !fc.from_ds && !fc.protected);
+
+        assert_eq!(header.receiver(), BROADCAST);
+        assert_eq!(header.transmitter(), Some(AP));
+        assert_eq!(header.bssid(), Some(AP));
+        assert_eq!(
+            header.sequence_control,
+            Some(SequenceControl {
+                fragment_number: 0,
+                sequence_number: 0x7a1,
+            })
+        );
+        assert_eq!(header.addr4, None);
+        assert_eq!(header.qos_control, None);
+        assert_eq!(header.body, &BEACON[MANAGEMENT_HEADER_LEN..]);
+    }
+
+    #[test]
+    fn qos_data_to_ds() {
+        let (frame, len) = data_frame(0x01, &[AP, STA, HOST], true);
+        let header = FrameHeader::parse(&frame[..len]).unwrap();
+        let fc = header.frame_control;
+        assert_eq!(fc.frame_type, FrameType::Data);
+        assert_eq!(fc.subtype, 8);
+        assert!(fc.to_ds && !fc.from_ds);
+
+        assert_eq!(header.receiver(), AP);
+        assert_eq!(header.transmitter(), Some(STA));
+        assert_eq!(header.bssid(), Some(AP));
+        assert_eq!(header.addr3, Some(HOST));
+        assert_eq!(header.addr4, None);
+        assert_eq!(header.qos_control, Some(6));
+        assert_eq!(header.body, &frame[26..len]);
+    }
+
+    #[test]
+    fn qos_data_from_ds() {
+        let (frame, len) = data_frame(0x02, &[STA, AP, HOST], true);
+        let header = FrameHeader::parse(&frame[..len]).unwrap();
+        assert!(!header.frame_control.to_ds && header.frame_control.from_ds);
+        assert_eq!(header.receiver(), STA);
+        assert_eq!(header.bssid(), Some(AP));
+        assert_eq!(header.qos_control, Some(6));
+        assert_eq!(header.body, &frame[26..len]);
+    }
+
+    #[test]
+    fn data_without_ds() {
+        let (frame, len) = data_frame(0x00, &[STA, PEER, AP], false);
+        let header = FrameHeader::parse(&frame[..len]).unwrap();
+        assert_eq!(header.bssid(), Some(AP));
+        assert_eq!(header.qos_control, None);
+        assert_eq!(header.body, &frame[24..len]);
+    }
+
+    #[test]
+    fn four_address_frame() {
+        let (frame, len) = data_frame(0x03, &[AP, PEER, HOST, STA], true);
+        let header = FrameHeader::parse(&frame[..len]).unwrap();
+        assert!(header.frame_control.to_ds && header.frame_control.from_ds);
+        assert_eq!(header.receiver(), AP);
+        assert_eq!(header.transmitter(), Some(PEER));
+        assert_eq!(header.addr3, Some(HOST));
+        assert_eq!(header.addr4, Some(STA));
+        assert_eq!(header.bssid(), None);
+        assert_eq!(header.qos_control, Some(6));
+        assert_eq!(header.body, &frame[32..len]);
+
+        // without QoS the body follows addr4
+        let (frame, len) = data_frame(0x03, &[AP, PEER, HOST, STA], false);
+        let header = FrameHeader::parse(&frame[..len]).unwrap();
+        assert_eq!(header.addr4, Some(STA));
+        assert_eq!(header.qos_control, None);
+        assert_eq!(header.body, &frame[30..len]);
+    }
+
+    #[test]
+    fn control_frames() {
+        // RTS with FCS
+        let rts = [
+            0xb4, 0x00, 0x5e, 0x01, 0x00, 0x1d, 0x7e, 0x4a, 0x6b, 0x10, 0xc8, 0x43, 0x57, 0x12,
+            0x34, 0x56, 0x1a, 0x2b, 0x3c, 0x4d,
+        ];
+        let header = FrameHeader::parse(&rts).unwrap();
+        assert_eq!(header.frame_control.frame_type, FrameType::Control);
+        assert_eq!(header.frame_control.subtype, 11);
+        assert_eq!(header.duration, 0x15e);
+        assert_eq!(header.receiver(), AP);
+        assert_eq!(header.transmitter(), Some(STA));
+        assert_eq!(header.sequence_control, None);
+        assert_eq!(header.bssid(), None);
+        assert_eq!(header.body, &rts[16..]);
+
+        // ACK and CTS only have the receiver
+        let ack = [0xd4, 0x00, 0x00, 0x00, 0xc8, 0x43, 0x57, 0x12, 0x34, 0x56];
+        let header = FrameHeader::parse(&ack).unwrap();
+        assert_eq!(header.frame_control.subtype, SUBTYPE_ACK);
+        assert_eq!(header.receiver(), STA);
+        assert_eq!(header.transmitter(), None);
+        assert!(header.body.is_empty());
+
+        let cts = [0xc4, 0x00, 0x3a, 0x01, 0xc8, 0x43, 0x57, 0x12, 0x34, 0x56];
+        let header = FrameHeader::parse(&cts).unwrap();
+        assert_eq!(header.frame_control.subtype, SUBTYPE_CTS);
+        assert_eq!(header.transmitter(), None);
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(FrameHeader::parse(&[]).is_none());
+        assert!(FrameHeader::parse(&BEACON[..1]).is_none());
+
+        for len in 0..MANAGEMENT_HEADER_LEN {
+            assert!(FrameHeader::parse(&BEACON[..len]).is_none(), "{}", len);
+        }
+        assert!(FrameHeader::parse(&BEACON[..MANAGEMENT_HEADER_LEN]).is_some());
+
+        let (frame, _) = data_frame(0x03, &[AP, PEER, HOST, STA], true);
+        for len in 0..32 {
+            assert!(FrameHeader::parse(&frame[..len]).is_none(), "{}", len);
+        }
+
+        let ack = [0xd4, 0x00, 0x00, 0x00, 0xc8, 0x43, 0x57, 0x12, 0x34, 0x56];
+        assert!(FrameHeader::parse(&ack[..9]).is_none());
+        let rts = [
+            0xb4, 0x00, 0x5e, 0x01, 0x00, 0x1d, 0x7e, 0x4a, 0x6b, 0x10, 0xc8, 0x43, 0x57, 0x12,
+            0x34, 0x56,
+        ];
+        assert!(FrameHeader::parse(&rts[..15]).is_none());
+    }
+
+    #[test]
+    fn frame_control_round_trip() {
+        for raw in [
+            0x0080u16, 0x0188, 0x0288, 0x0388, 0x00b4, 0x00d4, 0x4808, 0xffff,
+        ]
+        .iter()
+        {
+            assert_eq!(FrameControl::from_raw(*raw).to_raw(), *raw);
+        }
+    }
+
+    #[test]
+    fn written_beacon_parses() {
+        let mut buf = [0u8; 128];
+        let mut writer = FrameWriter::beacon(&mut buf, AP, "test", 6, 100).unwrap();
+        writer.vendor_element([0x00, 0x50, 0xf2], &[1, 2]).unwrap();
+        let frame = writer.as_bytes();
+
+        let header = FrameHeader::parse(frame).unwrap();
+        assert!(header.frame_control.is_management(SUBTYPE_BEACON));
+        assert_eq!(header.bssid(), Some(AP));
+        // beacon interval and SSID as in BEACON, only ESS in the capabilities
+        assert_eq!(&header.body[8..10], &BEACON[32..34]);
+        assert_eq!(&header.body[10..12], &[0x01, 0x00]);
+        assert_eq!(&header.body[12..18], &BEACON[36..42]);
+        assert_eq!(&frame[42..51], &[1, 4, 0x82, 0x84, 0x8b, 0x96, 3, 1, 6]);
+        assert_eq!(&frame[51..], &[221, 5, 0x00, 0x50, 0xf2, 1, 2]);
+
+        assert_eq!(
+            FrameWriter::beacon(&mut buf, AP, "test", 15, 100).err(),
+            Some(FrameError::InvalidChannel)
+        );
+        assert_eq!(
+            FrameWriter::beacon(&mut buf[..30], AP, "test", 6, 100).err(),
+            Some(FrameError::BufferTooSmall)
+        );
+    }
+}
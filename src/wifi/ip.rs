@@ -0,0 +1,67 @@
+use smoltcp::dhcp::Dhcpv4Config;
+use smoltcp::wire::{Ipv4Address, Ipv4Cidr};
+
+use crate::binary::wifi_mgmr_api;
+
+use super::{ControlError, WifiEvent};
+
+// the lease last reported to the blob, cleared when the connection is lost
+static mut STA_LEASE: Option<(Ipv4Cidr, Ipv4Address)> = None;
+
+/// Tells the blob about the lease we got, which moves its state from
+/// `ConnectedIpGetting` to `ConnectedIpGot`. Configs without an address are ignored.
+pub fn report_dhcp_config(config: &Dhcpv4Config) -> Result<(), ControlError> {
+    let cidr = match config.address {
+        Some(cidr) => cidr,
+        None => return Ok(()),
+    };
+
+    let netmask = cidr.netmask();
+    let gateway = config.router.unwrap_or(Ipv4Address::UNSPECIFIED);
+    let dns1 = config.dns_servers[0].unwrap_or(Ipv4Address::UNSPECIFIED);
+    let dns2 = config.dns_servers[1].unwrap_or(Ipv4Address::UNSPECIFIED);
+
+    let res = unsafe {
+        wifi_mgmr_api::wifi_mgmr_api_ip_got(
+            to_raw(cidr.address()),
+            to_raw(netmask),
+            to_raw(gateway),
+            to_raw(dns1),
+            to_raw(dns2),
+        )
+    };
+    if res != 0 {
+        return Err(ControlError::Failed(res));
+    }
+
+    riscv::interrupt::free(|_| unsafe {
+        STA_LEASE = Some((cidr, gateway));
+    });
+
+    Ok(())
+}
+
+/// The station's address and gateway of the lease last passed to
+/// [report_dhcp_config], `None` while not connected
+pub fn sta_ip() -> Option<(Ipv4Cidr, Ipv4Address)> {
+    riscv::interrupt::free(|_| unsafe { STA_LEASE })
+}
+
+/// Forgets the lease
+pub(crate) fn reset_state() {
+    riscv::interrupt::free(|_| unsafe {
+        STA_LEASE = None;
+    });
+}
+
+/// Must be called with interrupts disabled.
+pub(crate) unsafe fn on_event(event: WifiEvent) {
+    if event.is_disconnect() {
+        STA_LEASE = None;
+    }
+}
+
+/// The blob stores addresses in network byte order
+fn to_raw(address: Ipv4Address) -> u32 {
+    u32::from_le_bytes(address.0)
+}
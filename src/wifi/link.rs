@@ -0,0 +1,68 @@
+use crate::binary::wifi_mgmr;
+
+use super::connect::SSID_MAX_LEN;
+use super::{conn_result, wifi_state, WifiState};
+
+/// Information about the station's link, see [link_info]. There is no data
+/// rate - the blob only reports it for stations connected to our AP, see
+/// [super::StationInfo].
+#[derive(Debug, Clone, Copy)]
+pub struct LinkInfo {
+    pub state: WifiState,
+    /// Signal strength in dBm
+    pub rssi: i32,
+    pub channel: u8,
+    pub bssid: [u8; 6],
+    pub ssid: [u8; 32],
+    pub ssid_len: usize,
+    /// Status code of the last connection attempt
+    pub status_code: u16,
+    /// Reason code of the last disconnect
+    pub reason_code: u16,
+}
+
+impl LinkInfo {
+    pub fn ssid_str(&self) -> &str {
+        core::str::from_utf8(&self.ssid[..self.ssid_len]).unwrap_or("")
+    }
+}
+
+/// Reads state, signal strength and the AP we are connected to (or trying to)
+pub fn link_info() -> LinkInfo {
+    let mut rssi = 0;
+    let mut channel = 0;
+    let mut stat = wifi_mgmr::wifi_mgmr_sta_connect_ind_stat_info {
+        status_code: 0,
+        type_ind: 0,
+        ssid: [0u8; 32],
+        passphr: [0u8; 65],
+        psk: [0u8; 64],
+        bssid: [0u8; 6],
+        chan_freq: 0,
+        chan_band: 0,
+    };
+
+    unsafe {
+        wifi_mgmr::wifi_mgmr_rssi_get(&mut rssi);
+        wifi_mgmr::wifi_mgmr_channel_get(&mut channel);
+        wifi_mgmr::wifi_mgmr_sta_connect_ind_stat_get(&mut stat);
+    }
+
+    let (status_code, reason_code) = conn_result();
+    let ssid_len = stat
+        .ssid
+        .iter()
+        .position(|b| *b == 0)
+        .unwrap_or(SSID_MAX_LEN);
+
+    LinkInfo {
+        state: wifi_state(),
+        rssi,
+        channel: channel as u8,
+        bssid: stat.bssid,
+        ssid: stat.ssid,
+        ssid_len,
+        status_code,
+        reason_code,
+    }
+}
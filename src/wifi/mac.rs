@@ -0,0 +1,319 @@
+//! Where the MAC addresses come from. Only depends on `core`, the efuse
+//! registers are read by the caller.
+
+/// Number of MAC slots in the efuse
+pub const EFUSE_MAC_SLOTS: u8 = 3;
+
+/// Where `wifi_init` takes the station MAC from, the AP MAC is derived from it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacSource {
+    /// The most recently programmed efuse slot, falls back to `ChipId` if no slot is valid
+    Efuse,
+    /// Efuse slot 0 - 2. Slot 0 holds the factory MAC, 1 and 2 can be programmed later.
+    EfuseSlot(u8),
+    /// A locally administered unicast MAC
+    Custom([u8; 6]),
+    /// A locally administered MAC derived from the chip ID
+    ChipId,
+}
+
+impl Default for MacSource {
+    fn default() -> Self {
+        MacSource::Efuse
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacError {
+    AllZero,
+    /// The group bit is set
+    Multicast,
+    /// A custom MAC needs the locally administered bit
+    NotLocallyAdministered,
+    /// The efuse slot doesn't exist
+    InvalidSlot,
+    /// The efuse slot isn't programmed
+    SlotEmpty,
+    /// The zero bit count stored in the efuse doesn't match the MAC
+    ParityMismatch,
+}
+
+/// Checks that the MAC can be used by an interface
+pub fn validate_mac(mac: &[u8; 6]) -> Result<(), MacError> {
+    if *mac == [0u8; 6] {
+        return Err(MacError::AllZero);
+    }
+
+    if mac[0] & 0x01 != 0 {
+        return Err(MacError::Multicast);
+    }
+
+    Ok(())
+}
+
+pub fn is_locally_administered(mac: &[u8; 6]) -> bool {
+    mac[0] & 0x02 != 0
+}
+
+/// A custom MAC has to be a locally administered unicast address so it can't
+/// collide with a vendor assigned one
+pub fn validate_custom_mac(mac: &[u8; 6]) -> Result<(), MacError> {
+    validate_mac(mac)?;
+    if !is_locally_administered(mac) {
+        return Err(MacError::NotLocallyAdministered);
+    }
+    Ok(())
+}
+
+/// The MAC of an efuse slot given the two words of the slot. The high word
+/// holds the number of zero bits of the MAC in bits 16 - 21.
+pub fn mac_from_efuse_words(low: u32, high: u32) -> Result<[u8; 6], MacError> {
+    if low == 0 && high & 0x003f_ffff == 0 {
+        return Err(MacError::SlotEmpty);
+    }
+
+    let low = low.to_be_bytes();
+    let high = high.to_be_bytes();
+    let mac = [high[2], high[3], low[0], low[1], low[2], low[3]];
+
+    let zero_bits: u32 = mac.iter().map(|byte| byte.count_zeros()).sum();
+    if zero_bits & 0x3f != (u32::from_be_bytes(high) >> 16) & 0x3f {
+        return Err(MacError::ParityMismatch);
+    }
+
+    validate_mac(&mac)?;
+    Ok(mac)
+}
+
+/// A locally administered MAC derived from the 8 byte chip ID
+pub fn mac_from_chip_id(chip_id: [u8; 8]) -> Result<[u8; 6], MacError> {
+    if chip_id == [0u8; 8] {
+        return Err(MacError::SlotEmpty);
+    }
+
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in chip_id.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    let hash = hash.to_be_bytes();
+    let mut mac = [hash[0], hash[1], hash[2], hash[3], hash[4], hash[5]];
+    mac[0] = (mac[0] & 0xfc) | 0x02;
+    Ok(mac)
+}
+
+/// The AP uses the station's MAC with the locally administered bit set. If
+/// that's set already the last bit is flipped so both stay distinct.
+pub fn ap_mac_from_sta_mac(sta_mac: [u8; 6]) -> [u8; 6] {
+    let mut ap_mac = sta_mac;
+    ap_mac[0] |= 0x02;
+    if ap_mac == sta_mac {
+        ap_mac[5] ^= 0x01;
+    }
+    ap_mac
+}
+
+/// Resolves the source, `read_efuse` returns the low and high word of a slot
+pub fn resolve_mac<F>(source: MacSource, read_efuse: &F) -> Result<[u8; 6], MacError>
+where
+    F: Fn(u8) -> (u32, u32),
+{
+    match source {
+        MacSource::Efuse => (0..EFUSE_MAC_SLOTS)
+            .rev()
+            .find_map(|slot| resolve_mac(MacSource::EfuseSlot(slot), read_efuse).ok())
+            .map_or_else(|| resolve_mac(MacSource::ChipId, read_efuse), Ok),
+        MacSource::EfuseSlot(slot) => {
+            if slot >= EFUSE_MAC_SLOTS {
+                return Err(MacError::InvalidSlot);
+            }
+            let (low, high) = read_efuse(slot);
+            mac_from_efuse_words(low, high)
+        }
+        MacSource::Custom(mac) => validate_custom_mac(&mac).map(|_| mac),
+        MacSource::ChipId => {
+            // the chip ID is the content of the factory slot
+            let (low, high) = read_efuse(0);
+            let mut chip_id = [0u8; 8];
+            chip_id[..4].copy_from_slice(&low.to_le_bytes());
+            chip_id[4..].copy_from_slice(&high.to_le_bytes());
+            mac_from_chip_id(chip_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FACTORY_MAC: [u8; 6] = [0xc8, 0x43, 0x57, 0x12, 0x34, 0x56];
+    const SLOT1_MAC: [u8; 6] = [0xc8, 0x43, 0x57, 0x00, 0x00, 0x09];
+
+    /// The words of an efuse slot programmed with the MAC
+    fn efuse_words(mac: [u8; 6]) -> (u32, u32) {
+        let zero_bits: u32 = mac.iter().map(|byte| byte.count_zeros()).sum();
+        let low = u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]);
+        let high = (zero_bits << 16) | ((mac[0] as u32) << 8) | mac[1] as u32;
+        (low, high)
+    }
+
+    #[test]
+    fn efuse_words_to_mac() {
+        let (low, high) = efuse_words(FACTORY_MAC);
+        assert_eq!(mac_from_efuse_words(low, high), Ok(FACTORY_MAC));
+
+        // bits above the zero bit count don't matter
+        assert_eq!(
+            mac_from_efuse_words(low, high | 0xffc0_0000),
+            Ok(FACTORY_MAC)
+        );
+    }
+
+    #[test]
+    fn efuse_words_errors() {
+        assert_eq!(mac_from_efuse_words(0, 0), Err(MacError::SlotEmpty));
+        assert_eq!(
+            mac_from_efuse_words(0, 0xffc0_0000),
+            Err(MacError::SlotEmpty)
+        );
+
+        let (low, high) = efuse_words(FACTORY_MAC);
+        assert_eq!(
+            mac_from_efuse_words(low ^ 0x10, high),
+            Err(MacError::ParityMismatch)
+        );
+        assert_eq!(
+            mac_from_efuse_words(low, high ^ 0x0001_0000),
+            Err(MacError::ParityMismatch)
+        );
+
+        let (low, high) = efuse_words([0x01, 0x00, 0x5e, 0x00, 0x00, 0x01]);
+        assert_eq!(mac_from_efuse_words(low, high), Err(MacError::Multicast));
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(validate_mac(&FACTORY_MAC), Ok(()));
+        assert_eq!(validate_mac(&[0u8; 6]), Err(MacError::AllZero));
+        assert_eq!(validate_mac(&[0xff; 6]), Err(MacError::Multicast));
+
+        assert_eq!(
+            validate_custom_mac(&FACTORY_MAC),
+            Err(MacError::NotLocallyAdministered)
+        );
+        assert_eq!(validate_custom_mac(&[0x02, 0, 0, 0, 0, 1]), Ok(()));
+        assert_eq!(
+            validate_custom_mac(&[0x03, 0, 0, 0, 0, 1]),
+            Err(MacError::Multicast)
+        );
+        assert_eq!(validate_custom_mac(&[0u8; 6]), Err(MacError::AllZero));
+    }
+
+    #[test]
+    fn ap_mac() {
+        assert_eq!(
+            ap_mac_from_sta_mac(FACTORY_MAC),
+            [0xca, 0x43, 0x57, 0x12, 0x34, 0x56]
+        );
+
+        // a locally administered station MAC still gets a different AP MAC
+        let sta_mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let ap_mac = ap_mac_from_sta_mac(sta_mac);
+        assert_ne!(ap_mac, sta_mac);
+        assert_eq!(ap_mac, [0x02, 0x11, 0x22, 0x33, 0x44, 0x54]);
+        assert_eq!(validate_mac(&ap_mac), Ok(()));
+    }
+
+    #[test]
+    fn chip_id() {
+        let chip_id = [0x56, 0x34, 0x12, 0x57, 0x43, 0xc8, 0x2c, 0x00];
+        let mac = mac_from_chip_id(chip_id).unwrap();
+        assert_eq!(validate_custom_mac(&mac), Ok(()));
+        assert_eq!(mac_from_chip_id(chip_id), Ok(mac));
+
+        let mut other = chip_id;
+        other[0] ^= 1;
+        assert_ne!(mac_from_chip_id(other), Ok(mac));
+
+        assert_eq!(mac_from_chip_id([0u8; 8]), Err(MacError::SlotEmpty));
+    }
+
+    #[test]
+    fn resolve_newest_slot_first() {
+        let efuse = |slot: u8| match slot {
+            0 => efuse_words(FACTORY_MAC),
+            1 => efuse_words(SLOT1_MAC),
+            _ => (0, 0),
+        };
+
+        assert_eq!(resolve_mac(MacSource::Efuse, &efuse), Ok(SLOT1_MAC));
+        assert_eq!(
+            resolve_mac(MacSource::EfuseSlot(0), &efuse),
+            Ok(FACTORY_MAC)
+        );
+        assert_eq!(resolve_mac(MacSource::EfuseSlot(1), &efuse), Ok(SLOT1_MAC));
+        assert_eq!(
+            resolve_mac(MacSource::EfuseSlot(2), &efuse),
+            Err(MacError::SlotEmpty)
+        );
+        assert_eq!(
+            resolve_mac(MacSource::EfuseSlot(EFUSE_MAC_SLOTS), &efuse),
+            Err(MacError::InvalidSlot)
+        );
+    }
+
+    #[test]
+    fn resolve_skips_invalid_slots() {
+        let efuse = |slot: u8| match slot {
+            0 => efuse_words(FACTORY_MAC),
+            1 => {
+                let (low, high) = efuse_words(SLOT1_MAC);
+                (low ^ 1, high)
+            }
+            _ => efuse_words([0xff; 6]),
+        };
+
+        assert_eq!(resolve_mac(MacSource::Efuse, &efuse), Ok(FACTORY_MAC));
+    }
+
+    #[test]
+    fn resolve_falls_back_to_chip_id() {
+        // factory slot with a broken parity, the chip ID is still unique
+        let efuse = |slot: u8| match slot {
+            0 => {
+                let (low, high) = efuse_words(FACTORY_MAC);
+                (low, high ^ 0x0001_0000)
+            }
+            _ => (0, 0),
+        };
+
+        let (low, high) = efuse(0);
+        let mut chip_id = [0u8; 8];
+        chip_id[..4].copy_from_slice(&low.to_le_bytes());
+        chip_id[4..].copy_from_slice(&high.to_le_bytes());
+
+        let mac = resolve_mac(MacSource::Efuse, &efuse).unwrap();
+        assert_eq!(Ok(mac), mac_from_chip_id(chip_id));
+        assert_eq!(resolve_mac(MacSource::ChipId, &efuse), Ok(mac));
+        assert!(is_locally_administered(&mac));
+
+        // an unprogrammed efuse has no MAC at all
+        assert_eq!(
+            resolve_mac(MacSource::Efuse, &|_| (0, 0)),
+            Err(MacError::SlotEmpty)
+        );
+    }
+
+    #[test]
+    fn resolve_custom() {
+        let efuse = |_| panic!("custom MACs don't read the efuse");
+        let mac = [0x02, 0x00, 0x00, 0x12, 0x34, 0x56];
+        assert_eq!(resolve_mac(MacSource::Custom(mac), &efuse), Ok(mac));
+        assert_eq!(
+            resolve_mac(MacSource::Custom(FACTORY_MAC), &efuse),
+            Err(MacError::NotLocallyAdministered)
+        );
+    }
+}
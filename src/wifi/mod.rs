@@ -2,13 +2,95 @@ use embedded_time::duration::Milliseconds;
 use smoltcp::phy::{Device, DeviceCapabilities, RxToken, TxToken};
 use smoltcp::wire::EthernetAddress;
 
-use crate::binary::wifi_mgmr::{self, wifi_mgmr_drv_init, _WIFI_EVENT_CODE_WIFI_ON_GOT_IP};
+use crate::binary::bl_wifi;
+use crate::binary::wifi_mgmr::{self, wifi_mgmr_drv_init};
 use crate::binary::wifi_mgmr_api;
 use crate::compat::common::EMULATED_TIMER;
 use crate::compat::{common::EmulatedTimer, get_time, work_queue::do_work};
-use crate::{binary::bl_wifi, compat::queue::SimpleQueue};
 use crate::{log, print, println};
 
+mod ap;
+pub use ap::{
+    ap_stations, is_ap_started, kick_station, start_ap, stop_ap, ApError, StationInfo,
+    AP_MAX_STATIONS,
+};
+
+mod connect;
+pub use connect::{
+    channel_to_freq, conn_result, connect, connect_async, connect_with_options,
+    connect_with_options_async, connect_with_psk, status_code_str, ConnectError, ConnectHandle,
+    ConnectOptions, PASSPHRASE_MAX_LEN, PASSPHRASE_MIN_LEN, SSID_MAX_LEN,
+};
+
+mod config;
+pub use config::{WifiConfig, WifiInitError, HOSTNAME_MAX_LEN};
+
+mod control;
+pub use control::{deinit, disconnect, idle, sta_down_requested, ControlError};
+
+mod country;
+pub use country::{regulatory_domain, set_country_code, CountryError, RegulatoryDomain};
+
+mod event;
+use event::EventQueue;
+pub use event::{WifiEvent, STATUS_CODE_NO_AP_FOUND, STATUS_CODE_PSK_FAILURE};
+
+pub mod ieee80211;
+
+mod ip;
+pub use ip::{report_dhcp_config, sta_ip};
+
+mod link;
+pub use link::{link_info, LinkInfo};
+
+mod mac;
+pub use mac::{
+    ap_mac_from_sta_mac, is_locally_administered, mac_from_chip_id, mac_from_efuse_words,
+    validate_custom_mac, validate_mac, MacError, MacSource, EFUSE_MAC_SLOTS,
+};
+
+#[cfg(feature = "packet-trace")]
+mod trace;
+#[cfg(feature = "packet-trace")]
+pub use trace::{packet_trace, set_packet_trace, TraceFilter};
+
+mod power;
+pub use power::{
+    power_save, power_save_state, set_power_save, PowerSave, PowerSaveError, PowerSaveMode,
+    PowerSaveState,
+};
+
+mod profiles;
+pub use profiles::{NetworkProfile, NetworkProfiles, ProfileError, ProfileStatus, PROFILES_MAX};
+
+pub mod psk;
+
+mod raw;
+pub use raw::{send_raw_80211, RawSendError, RAW_FRAME_MAX_LEN};
+
+mod reconnect;
+pub use reconnect::{ReconnectConfig, ReconnectStatus, ReconnectSupervisor};
+
+mod rx;
+use rx::{DataFrame, RxQueue};
+pub use rx::{RxStats, RX_QUEUE_SIZE_DEFAULT, RX_QUEUE_SIZE_MAX};
+
+mod scan;
+pub use scan::{
+    scan, scan_async, AuthMode, Cipher, ScanConfig, ScanError, ScanHandle, ScanItem, SCAN_ITEMS_MAX,
+};
+
+mod sniffer;
+pub use sniffer::{Sniffer, SnifferError};
+
+mod stack;
+pub use stack::{IpConfig, NetworkResources, NetworkStack};
+
+mod state;
+pub use state::{
+    clear_state_history, state_history, wifi_state, StateTransition, WifiState, STATE_HISTORY_SIZE,
+};
+
 extern "C" {
     static mut __wifi_bss_start: u32;
 
@@ -28,26 +110,48 @@ extern "C" {
 
     pub fn bl_output(bl_hw: *const bl_wifi::bl_hw, p: *mut u8, tot_len: usize, is_sta: i32) -> i32;
 
-    pub static wifiMgmr: wifi_mgmr::wifi_mgmr;
+    pub static mut wifiMgmr: wifi_mgmr::wifi_mgmr;
 
     pub static bl606a0_sta: bl_wifi::net_device;
 }
 
-pub static mut WIFI_CONNECTED: bool = false;
-struct DataFrame {
-    len: usize,
-    data: *mut u8,
-}
-
-static mut DATA_QUEUE_RX: SimpleQueue<DataFrame> = SimpleQueue::new();
+static mut CONNECTED: bool = false;
+// bumped for every disconnect event so pollers notice links which bounced in between
+static mut DISCONNECTS: u32 = 0;
+// set by disconnect, idle and deinit until the next connect, pauses the supervisors
+static mut STA_DOWN_REQUESTED: bool = false;
+// set once wifi_init started the blob, it can't be started again
+static mut WIFI_STARTED: bool = false;
+static mut INIT_DONE: bool = false;
+static mut MGMR_DONE: bool = false;
+
+static mut EVENT_QUEUE: EventQueue = EventQueue::new();
+static mut EVENT_CALLBACK: Option<fn(WifiEvent)> = None;
+
+static mut DATA_QUEUE_RX: RxQueue = RxQueue::new();
+static mut DATA_QUEUE_RX_AP: RxQueue = RxQueue::new();
+
+static mut STA_MAC: [u8; 6] = [0u8; 6];
+static mut AP_MAC: [u8; 6] = [0u8; 6];
+
+// set while the station is associated, used to route broadcasts
+static mut STA_LINK_UP: bool = false;
+// stations connected to our AP, their frames are routed to the AP device
+static mut AP_CLIENTS: [Option<[u8; 6]>; AP_MAX_STATIONS] = [None; AP_MAX_STATIONS];
+// a station joined or left, AP_CLIENTS gets refreshed outside the event callback
+static mut AP_CLIENTS_STALE: bool = false;
+
+pub const TX_QUEUE_SIZE: usize = 4;
+const TX_BUFFER_SIZE: usize = 1650;
+// the blob needs 128 bytes in front of the data for the 802.11 header
+const TX_HEADROOM: usize = 128;
 
 #[link_section = ".wifi_ram.txbuff"]
-static mut TX_BUFFER: [u8; 1650] = [0u8; 1650]; // should be a queue
+static mut TX_BUFFERS: [[u8; TX_BUFFER_SIZE]; TX_QUEUE_SIZE] =
+    [[0u8; TX_BUFFER_SIZE]; TX_QUEUE_SIZE];
+static mut TX_BUFFER_IN_USE: [bool; TX_QUEUE_SIZE] = [false; TX_QUEUE_SIZE];
 pub static mut TX_QUEUED: bool = false;
 
-static mut SCAN_IN_PROGRESS: bool = false;
-static mut LAST_SCAN_RESULT: [Option<ScanItem>; 50] = [None; 50];
-
 pub fn wifi_pre_init() {
     unsafe {
         use core::{mem, ptr};
@@ -65,112 +169,170 @@ pub fn wifi_pre_init() {
     // bl602_set_em_sel_bl602_glb_em_8kb();
 }
 
-pub fn wifi_init() {
-    let mut mac = get_mac();
+/// Initializes wifi and waits until the blob is ready. This can only be done
+/// once, also after `deinit` or a failed `wifi_init`.
+pub fn wifi_init(config: WifiConfig) -> Result<(), WifiInitError> {
+    config.validate()?;
+    let domain = RegulatoryDomain::from_country_code(config.country)
+        .ok_or(WifiInitError::Country(CountryError::UnknownCountry))?;
+
+    let mut sta_mac = mac_from_source(config.mac_source).map_err(WifiInitError::Mac)?;
+    let mut ap_mac = ap_mac_from_sta_mac(sta_mac);
+
+    let started = riscv::interrupt::free(|_| unsafe {
+        let started = WIFI_STARTED;
+        WIFI_STARTED = true;
+        started
+    });
+    if started {
+        return Err(WifiInitError::AlreadyInitialized);
+    }
+
     println!("MAC address");
-    for x in mac.iter() {
+    for x in sta_mac.iter() {
         print!("{:2x} ", *x);
     }
     print!("\r\n");
 
-    let mut conf = crate::binary::wifi_mgmr::wifi_conf_t {
-        country_code: [b'C', b'N', 0],
-        channel_nums: 13,
-    };
+    riscv::interrupt::free(|_| unsafe {
+        STA_MAC = sta_mac;
+        AP_MAC = ap_mac;
+    });
+
+    let mut conf = domain.wifi_conf();
+    country::init_regulatory_domain(domain);
 
     unsafe {
-        crate::binary::bl_wifi::bl_wifi_ap_mac_addr_set(&mut mac as *mut _);
-        crate::binary::bl_wifi::bl_wifi_sta_mac_addr_set(&mut mac as *mut _);
+        crate::binary::bl_wifi::bl_wifi_ap_mac_addr_set(&mut ap_mac as *mut _);
+        crate::binary::bl_wifi::bl_wifi_sta_mac_addr_set(&mut sta_mac as *mut _);
 
-        let mut my_ssid = [b't', b'e', b's', b't', 0];
-        crate::binary::wifi_mgmr_api::wifi_mgmr_sta_ssid_set(&mut my_ssid as *mut _);
-        crate::binary::wifi_mgmr_api::wifi_mgmr_sta_mac_set(&mut mac as *mut _);
+        crate::binary::wifi_mgmr_api::wifi_mgmr_sta_mac_set(&mut sta_mac as *mut _);
+        crate::binary::wifi_mgmr_api::wifi_mgmr_ap_mac_set(&mut ap_mac as *mut _);
 
         bl_pm_init();
         wifi_main_init();
         ipc_emb_notify();
         wifi_mgmr_drv_init(&mut conf);
+    }
 
-        for _ in 0..250000 {}
+    // the firmware signals INIT_DONE once it's running
+    if !wait_for(|| unsafe { INIT_DONE }, config.init_timeout) {
+        return Err(WifiInitError::Timeout);
+    }
 
+    unsafe {
         wifi_mgmr_tsk_init();
+    }
 
-        crate::binary::wifi_mgmr_api::wifi_mgmr_sta_autoconnect_disable();
+    if !wait_for(
+        || unsafe { MGMR_DONE && wifiMgmr.ready != 0 },
+        config.init_timeout,
+    ) {
+        return Err(WifiInitError::Timeout);
     }
-}
 
-pub fn get_mac() -> [u8; 6] {
     unsafe {
-        let mac_lo = (0x40007014 as *const u32).read_volatile();
-        let mac_hi = (0x40007018 as *const u32).read_volatile();
+        if let Some(hostname) = config.hostname {
+            wifiMgmr.hostname = [0u8; 32];
+            wifiMgmr.hostname[..hostname.len()].copy_from_slice(hostname.as_bytes());
+        }
 
-        let mac = [
-            ((mac_hi & 0xff00) >> 8) as u8,
-            (mac_hi & 0xff) as u8,
-            ((mac_lo & 0xff000000) >> 24) as u8,
-            ((mac_lo & 0xff0000) >> 16) as u8,
-            ((mac_lo & 0xff00) >> 8) as u8,
-            (mac_lo & 0xff) as u8,
-        ];
-        mac
+        if config.autoconnect {
+            crate::binary::wifi_mgmr_api::wifi_mgmr_sta_autoconnect_enable();
+        } else {
+            crate::binary::wifi_mgmr_api::wifi_mgmr_sta_autoconnect_disable();
+        }
+
+        if let Some(beacon_interval) = config.beacon_interval {
+            let res = crate::binary::wifi_mgmr_api::wifi_mgmr_beacon_interval_set(beacon_interval);
+            if res != 0 {
+                return Err(WifiInitError::Failed(res));
+            }
+        }
+    }
+
+    if let Some(power_save) = config.power_save {
+        set_power_save(power_save).map_err(WifiInitError::PowerSave)?;
     }
+
+    Ok(())
 }
 
-pub fn wifi_scan() -> core::result::Result<[Option<ScanItem>; 50], ()> {
-    unsafe {
-        if SCAN_IN_PROGRESS {
-            return Err(());
-        }
+/// Busy waits until the condition is met, false on timeout
+fn wait_for(condition: impl Fn() -> bool, timeout: Milliseconds) -> bool {
+    let started = get_time().0;
 
-        SCAN_IN_PROGRESS = true;
-        wifi_mgmr::wifi_mgmr_scan(core::ptr::null_mut(), Some(scan_cb));
-        while SCAN_IN_PROGRESS {}
+    loop {
+        if riscv::interrupt::free(|_| condition()) {
+            return true;
+        }
 
-        Ok(LAST_SCAN_RESULT.clone())
+        if get_time().0.wrapping_sub(started) >= timeout.0 {
+            return false;
+        }
     }
 }
 
-pub fn connect_sta(arg_ssid: &str, arg_psk: &str) {
-    let mut ssid = [0u8; 33];
-    let mut psk = [0u8; 64];
+/// The MAC `wifi_init` uses by default, see [MacSource::Efuse]
+pub fn get_mac() -> Result<[u8; 6], MacError> {
+    mac_from_source(MacSource::Efuse)
+}
 
-    ssid[0..(arg_ssid.len())].copy_from_slice(arg_ssid.as_bytes());
-    psk[0..(arg_psk.len())].copy_from_slice(arg_psk.as_bytes());
+/// Resolves and validates the MAC of the source
+pub fn mac_from_source(source: MacSource) -> Result<[u8; 6], MacError> {
+    mac::resolve_mac(source, &read_efuse_mac_slot)
+}
+
+/// The low and high word of an efuse MAC slot
+fn read_efuse_mac_slot(slot: u8) -> (u32, u32) {
+    const EFUSE_BASE: usize = 0x4000_7000;
+    let (low, high) = match slot {
+        0 => (0x14, 0x18),
+        1 => (0x74, 0x78),
+        _ => (0x6c, 0x70),
+    };
 
     unsafe {
-        wifi_mgmr_api::wifi_mgmr_api_connect(
-            &mut ssid as *mut _,
-            &mut psk as *mut _,
-            core::ptr::null_mut(), // &mut ext_param as *mut _,
-        );
-
-        while !WIFI_CONNECTED {
-            // wait until we are connected
-        }
+        (
+            ((EFUSE_BASE + low) as *const u32).read_volatile(),
+            ((EFUSE_BASE + high) as *const u32).read_volatile(),
+        )
     }
 }
 
+/// MAC address of the given interface, valid after `wifi_init`
+pub fn mac_address(interface: WifiInterface) -> [u8; 6] {
+    riscv::interrupt::free(|_| unsafe {
+        match interface {
+            WifiInterface::Sta => STA_MAC,
+            WifiInterface::Ap => AP_MAC,
+        }
+    })
+}
+
+/// Scans all channels and waits for the result. See [scan] for more options.
+pub fn wifi_scan() -> core::result::Result<[Option<ScanItem>; SCAN_ITEMS_MAX], ()> {
+    scan(&ScanConfig::default()).map_err(|_| ())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn bl602_net_notify(event: u32, data: *mut u8, len: usize) -> i32 {
     // event: notify type, tx done or received new data
     // data: The data of the event, may be NULL
     // len: data length
 
-    println!("bl602_net_notify {} {:p} {}", event, data, len);
-
     let is_rx = (event & 0x2) != 0;
     let is_tx_done = (event & 0x1) != 0;
 
     riscv::interrupt::free(|_| {
         if is_rx {
-            if !DATA_QUEUE_RX.is_full() {
-                DATA_QUEUE_RX.enqueue(DataFrame {
-                    len: len,
-                    data: data,
-                });
-            }
+            let frame = core::slice::from_raw_parts(data, len);
+            rx_queue(rx_interface(frame)).enqueue(DataFrame {
+                len: len,
+                data: data,
+            });
         } else if is_tx_done {
-            // nothing here
+            release_tx_buffer(data);
         }
     });
 
@@ -178,55 +340,245 @@ pub unsafe extern "C" fn bl602_net_notify(event: u32, data: *mut u8, len: usize)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn bl602_netdev_free_txbuf(_buf: *mut u8) {
-    println!("bl602_netdev_free_txbuf called");
+pub unsafe extern "C" fn bl602_netdev_free_txbuf(buf: *mut u8) {
+    log!("bl602_netdev_free_txbuf called {:p}", buf);
+
+    riscv::interrupt::free(|_| {
+        release_tx_buffer(buf);
+    });
+}
+
+fn claim_tx_buffer() -> Option<usize> {
+    riscv::interrupt::free(|_| unsafe {
+        let index = TX_BUFFER_IN_USE.iter().position(|in_use| !*in_use)?;
+        TX_BUFFER_IN_USE[index] = true;
+        Some(index)
+    })
+}
+
+/// Returns the TX buffer containing `buf` to the pool.
+/// Must be called with interrupts disabled.
+unsafe fn release_tx_buffer(buf: *mut u8) {
+    if buf.is_null() {
+        return;
+    }
+
+    // the blob might hand back the start of the buffer or the start of the data
+    let addr = buf as usize;
+    for i in 0..TX_QUEUE_SIZE {
+        let start = &TX_BUFFERS[i] as *const _ as usize;
+        if addr >= start && addr < start + TX_BUFFER_SIZE {
+            TX_BUFFER_IN_USE[i] = false;
+        }
+    }
+}
+
+/// Must be called with interrupts disabled.
+unsafe fn rx_queue(interface: WifiInterface) -> &'static mut RxQueue {
+    match interface {
+        WifiInterface::Sta => &mut DATA_QUEUE_RX,
+        WifiInterface::Ap => &mut DATA_QUEUE_RX_AP,
+    }
+}
+
+/// Decides which interface a received frame belongs to.
+/// Must be called with interrupts disabled.
+unsafe fn rx_interface(frame: &[u8]) -> WifiInterface {
+    if !is_ap_started() || frame.len() < 12 {
+        return WifiInterface::Sta;
+    }
+
+    let dst = &frame[0..6];
+    let src = &frame[6..12];
+
+    let group = dst[0] & 0x01 != 0;
+
+    if dst == AP_MAC || AP_CLIENTS.iter().flatten().any(|client| client == src) {
+        WifiInterface::Ap
+    } else if dst == STA_MAC {
+        WifiInterface::Sta
+    } else if !STA_LINK_UP {
+        WifiInterface::Ap
+    } else if group && AP_CLIENTS_STALE {
+        // most likely a station which just joined and isn't in AP_CLIENTS yet,
+        // e.g. its DHCP discover
+        WifiInterface::Ap
+    } else {
+        WifiInterface::Sta
+    }
+}
+
+/// Reads the stations connected to our AP after one joined or left. Calls
+/// into the blob so it's not done from the event callback.
+fn update_ap_clients() {
+    // cleared before reading so a station joining meanwhile marks it stale again
+    let stale = riscv::interrupt::free(|_| unsafe {
+        let stale = AP_CLIENTS_STALE;
+        AP_CLIENTS_STALE = false;
+        stale
+    });
+    if !stale {
+        return;
+    }
+
+    let mut clients = [None; AP_MAX_STATIONS];
+    for (client, station) in clients.iter_mut().zip(ap_stations().iter()) {
+        *client = station.map(|station| station.mac);
+    }
+
+    riscv::interrupt::free(|_| unsafe {
+        AP_CLIENTS = clients;
+    });
+}
+
+/// Sets how many received frames are buffered per interface until they are consumed.
+/// Frames arriving while the queue is full are dropped.
+pub fn set_rx_queue_depth(depth: usize) {
+    riscv::interrupt::free(|_| unsafe {
+        DATA_QUEUE_RX.set_depth(depth);
+        DATA_QUEUE_RX_AP.set_depth(depth);
+    });
+}
+
+pub fn rx_stats(interface: WifiInterface) -> RxStats {
+    riscv::interrupt::free(|_| unsafe { rx_queue(interface).stats() })
+}
+
+pub fn reset_rx_stats() {
+    riscv::interrupt::free(|_| unsafe {
+        DATA_QUEUE_RX.reset_stats();
+        DATA_QUEUE_RX_AP.reset_stats();
+    });
+}
+
+fn tx_buffer_available() -> bool {
+    riscv::interrupt::free(|_| unsafe { TX_BUFFER_IN_USE.iter().any(|in_use| !*in_use) })
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn bl602_net_event(evt: i32, val: u32) {
     // evt e.g. CODE_WIFI_ON_CONNECTED, CODE_WIFI_ON_GOT_IP, ...
+    let event = WifiEvent::from_raw(evt, val);
+
+    match event {
+        WifiEvent::ApStationAdded { .. } | WifiEvent::ApStationRemoved { .. } => {
+            riscv::interrupt::free(|_| AP_CLIENTS_STALE = true);
+        }
+        WifiEvent::ApStopped => {
+            riscv::interrupt::free(|_| {
+                AP_CLIENTS = [None; AP_MAX_STATIONS];
+                AP_CLIENTS_STALE = false;
+            });
+        }
+        _ => (),
+    }
 
-    println!("bl602_net_event called {} {}", evt, val);
+    riscv::interrupt::free(|_| {
+        if event == WifiEvent::InitDone {
+            INIT_DONE = true;
+        } else if event == WifiEvent::MgmrDone {
+            MGMR_DONE = true;
+        } else if event == WifiEvent::Connected {
+            STA_LINK_UP = true;
+        } else if event == WifiEvent::GotIp {
+            STA_LINK_UP = true;
+            CONNECTED = true;
+        } else if event.is_disconnect() {
+            STA_LINK_UP = false;
+            CONNECTED = false;
+            DISCONNECTS = DISCONNECTS.wrapping_add(1);
+        }
 
-    if evt == _WIFI_EVENT_CODE_WIFI_ON_GOT_IP {
-        WIFI_CONNECTED = true;
+        connect::on_event(event);
+        ip::on_event(event);
+        state::on_event(event);
+        power::on_event(event);
+        EVENT_QUEUE.enqueue(event);
+    });
+
+    if let Some(callback) = EVENT_CALLBACK {
+        callback(event);
     }
 }
 
-pub unsafe extern "C" fn scan_cb(
-    _data: *mut crate::binary::c_types::c_void,
-    _param: *mut crate::binary::c_types::c_void,
-) {
-    println!("SCAN CALLBACK");
+/// True if we are connected to an AP and got an IP address
+pub fn is_connected() -> bool {
+    riscv::interrupt::free(|_| unsafe { CONNECTED })
+}
 
-    for i in 0..50 {
-        let item = wifiMgmr.scan_items[i];
-        if item.is_used != 0 {
-            LAST_SCAN_RESULT[i] = Some(ScanItem {
-                ssid: item.ssid.clone(),
-                channel: item.channel,
-                rssi: item.rssi,
-                bssid: item.bssid.clone(),
-            });
-        } else {
-            LAST_SCAN_RESULT[i] = None;
-        }
+/// True if the station is associated, it might not have an IP address yet
+pub fn is_link_up() -> bool {
+    riscv::interrupt::free(|_| unsafe { STA_LINK_UP })
+}
+
+/// Number of disconnect events so far. Compare two values to find out if the
+/// connection got lost in between, even if it's up again by now.
+pub fn disconnect_count() -> u32 {
+    riscv::interrupt::free(|_| unsafe { DISCONNECTS })
+}
+
+/// Returns the oldest event not yet seen by the application.
+pub fn poll_event() -> Option<WifiEvent> {
+    riscv::interrupt::free(|_| unsafe { EVENT_QUEUE.dequeue() })
+}
+
+/// Registers a function which gets called for every event.
+/// It's called from the context of the wifi tasks so it should return quickly.
+pub fn set_event_callback(callback: Option<fn(WifiEvent)>) {
+    riscv::interrupt::free(|_| unsafe {
+        EVENT_CALLBACK = callback;
+    });
+}
+
+/// Turns a NUL terminated string owned by the blob into a `&str`
+unsafe fn str_from_c(c_str: *const u8) -> &'static str {
+    if c_str.is_null() {
+        return "";
     }
 
-    SCAN_IN_PROGRESS = false;
+    let mut len = 0;
+    while *c_str.add(len) != 0 {
+        len += 1;
+    }
+
+    core::str::from_utf8(core::slice::from_raw_parts(c_str, len)).unwrap_or("")
 }
 
+/// Sets the MAC of the interface the device is bound to
 pub fn init_mac(ethernet: &mut smoltcp::iface::EthernetInterface<WifiDevice>) {
-    let mac = get_mac();
+    let mac = mac_address(ethernet.device().interface());
     let addr = EthernetAddress::from_bytes(&mac);
     ethernet.set_ethernet_addr(addr);
 }
 
-pub struct WifiDevice {}
+/// The virtual interface a `WifiDevice` is bound to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WifiInterface {
+    Sta,
+    Ap,
+}
+
+pub struct WifiDevice {
+    interface: WifiInterface,
+}
 
 impl WifiDevice {
+    /// Creates a device for the station interface
     pub fn new() -> WifiDevice {
-        WifiDevice {}
+        WifiDevice {
+            interface: WifiInterface::Sta,
+        }
+    }
+
+    /// Creates a device for the access point interface
+    pub fn new_ap() -> WifiDevice {
+        WifiDevice {
+            interface: WifiInterface::Ap,
+        }
+    }
+
+    pub fn interface(&self) -> WifiInterface {
+        self.interface
     }
 }
 
@@ -237,60 +589,74 @@ impl<'a> Device<'a> for WifiDevice {
     type TxToken = WifiTxToken;
 
     fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
-        let available = unsafe { !DATA_QUEUE_RX.is_empty() };
+        let available = riscv::interrupt::free(|_| unsafe { !rx_queue(self.interface).is_empty() });
 
         if available {
-            Some((WifiRxToken::default(), WifiTxToken::default()))
+            Some((
+                WifiRxToken {
+                    interface: self.interface,
+                },
+                WifiTxToken {
+                    interface: self.interface,
+                },
+            ))
         } else {
             None
         }
     }
 
     fn transmit(&'a mut self) -> Option<Self::TxToken> {
-        Some(WifiTxToken::default())
+        if tx_buffer_available() {
+            Some(WifiTxToken {
+                interface: self.interface,
+            })
+        } else {
+            None
+        }
     }
 
     fn capabilities(&self) -> smoltcp::phy::DeviceCapabilities {
         let mut caps = DeviceCapabilities::default();
         caps.max_transmission_unit = 1514;
-        caps.max_burst_size = Some(1);
+        caps.max_burst_size = Some(TX_QUEUE_SIZE);
         caps
     }
 }
 
-#[derive(Debug, Default)]
-pub struct WifiRxToken {}
+#[derive(Debug)]
+pub struct WifiRxToken {
+    interface: WifiInterface,
+}
 
 impl RxToken for WifiRxToken {
     fn consume<R, F>(self, _timestamp: smoltcp::time::Instant, f: F) -> smoltcp::Result<R>
     where
         F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
     {
-        unsafe {
-            while !DATA_QUEUE_RX.is_empty() {
-                let element = DATA_QUEUE_RX.dequeue();
+        let element = riscv::interrupt::free(|_| unsafe { rx_queue(self.interface).dequeue() });
 
-                match element {
-                    Some(data) => {
-                        let mut buffer = core::slice::from_raw_parts_mut(data.data, data.len);
+        match element {
+            Some(data) => unsafe {
+                let mut buffer = core::slice::from_raw_parts_mut(data.data, data.len);
 
-                        dump_packet_info(&buffer);
+                #[cfg(feature = "packet-trace")]
+                trace::trace_frame(trace::Direction::Rx, self.interface, &buffer);
 
-                        let res = f(&mut buffer);
-                        bl_free_rx_buffer(data.data);
-                        return res;
-                    }
-                    None => {}
-                }
-            }
+                let res = f(&mut buffer);
+                riscv::interrupt::free(|_| {
+                    rx_queue(self.interface).free(data);
+                });
+                res
+            },
+            None => Err(smoltcp::Error::Exhausted),
         }
-
-        Err(smoltcp::Error::Exhausted)
     }
 }
 
-#[derive(Debug, Default)]
-pub struct WifiTxToken {}
+#[derive(Debug)]
+pub struct WifiTxToken {
+    interface: WifiInterface,
+}
 
 impl TxToken for WifiTxToken {
     fn consume<R, F>(
@@ -302,91 +668,57 @@ impl TxToken for WifiTxToken {
     where
         F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
     {
-        // there are 128 bytes needed in front of the data
-        let res = unsafe { f(&mut TX_BUFFER[128..(128 + len)]) };
-
-        unsafe {
-            dump_packet_info(&TX_BUFFER[128..(128 + len)]);
+        if len > TX_BUFFER_SIZE - TX_HEADROOM {
+            return Err(smoltcp::Error::Truncated);
         }
 
+        let index = match claim_tx_buffer() {
+            Some(index) => index,
+            None => return Err(smoltcp::Error::Exhausted),
+        };
+
+        let mut buffer = unsafe { &mut TX_BUFFERS[index][TX_HEADROOM..(TX_HEADROOM + len)] };
+        let res = f(&mut buffer);
+
         match res {
             Ok(_) => {
-                let is_sta = 1; // for now we are always STA
+                #[cfg(feature = "packet-trace")]
+                trace::trace_frame(trace::Direction::Tx, self.interface, &buffer);
+
+                let is_sta = (self.interface == WifiInterface::Sta) as i32;
                 unsafe {
-                    if TX_QUEUED {
-                        // for the future we should have more TX buffers
+                    let res = bl_output(bl606a0_sta.bl_hw, buffer.as_mut_ptr(), len, is_sta);
+
+                    riscv::interrupt::free(|_| {
+                        if res != 0 {
+                            TX_BUFFER_IN_USE[index] = false;
+                        } else {
+                            TX_QUEUED = true;
+                        }
+                    });
+
+                    if res != 0 {
                         return Err(smoltcp::Error::Exhausted);
                     }
-
-                    bl_output(
-                        bl606a0_sta.bl_hw,
-                        (&mut TX_BUFFER as *mut u8).offset(128),
-                        len,
-                        is_sta,
-                    );
-                    TX_QUEUED = true;
                 }
             }
-            Err(_) => {}
+            Err(_) => unsafe {
+                riscv::interrupt::free(|_| {
+                    TX_BUFFER_IN_USE[index] = false;
+                });
+            },
         }
 
         res
     }
 }
 
-fn dump_packet_info(buffer: &[u8]) {
-    let ef = smoltcp::wire::EthernetFrame::new_unchecked(buffer);
-    println!(
-        "src={:x?} dst={:x?} type={:x?}",
-        ef.src_addr(),
-        ef.dst_addr(),
-        ef.ethertype()
-    );
-    match ef.ethertype() {
-        smoltcp::wire::EthernetProtocol::Ipv4 => {
-            let ip = smoltcp::wire::Ipv4Packet::new_unchecked(ef.payload());
-            println!(
-                "src={:?} dst={:?} proto={:x?}",
-                ip.src_addr(),
-                ip.dst_addr(),
-                ip.protocol()
-            );
-
-            match ip.protocol() {
-                smoltcp::wire::IpProtocol::HopByHop => {}
-                smoltcp::wire::IpProtocol::Icmp => {}
-                smoltcp::wire::IpProtocol::Igmp => {}
-                smoltcp::wire::IpProtocol::Tcp => {
-                    let tp = smoltcp::wire::TcpPacket::new_unchecked(ip.payload());
-                    println!("src={:?} dst={:?}", tp.src_port(), tp.dst_port());
-                }
-                smoltcp::wire::IpProtocol::Udp => {
-                    let up = smoltcp::wire::UdpPacket::new_unchecked(ip.payload());
-                    println!("src={:?} dst={:?}", up.src_port(), up.dst_port());
-                }
-                smoltcp::wire::IpProtocol::Ipv6Route => {}
-                smoltcp::wire::IpProtocol::Ipv6Frag => {}
-                smoltcp::wire::IpProtocol::Icmpv6 => {}
-                smoltcp::wire::IpProtocol::Ipv6NoNxt => {}
-                smoltcp::wire::IpProtocol::Ipv6Opts => {}
-                smoltcp::wire::IpProtocol::Unknown(_) => {}
-            }
-        }
-        smoltcp::wire::EthernetProtocol::Arp => {
-            let ap = smoltcp::wire::ArpPacket::new_unchecked(ef.payload());
-            println!(
-                "src={:x?} dst={:x?} src proto addr={:x?}",
-                ap.source_hardware_addr(),
-                ap.target_hardware_addr(),
-                ap.source_protocol_addr()
-            );
-        }
-        smoltcp::wire::EthernetProtocol::Ipv6 => {}
-        smoltcp::wire::EthernetProtocol::Unknown(_) => {}
-    }
-}
-
 pub fn trigger_transmit_if_needed() {
+    // called from the network loop regularly, not from the wifi tasks
+    update_ap_clients();
+    state::poll();
+    power::poll();
+
     unsafe {
         let trigger = riscv::interrupt::free(|_| {
             if TX_QUEUED {
@@ -440,11 +772,3 @@ pub extern "C" fn wifi_worker_task2() {
         do_work(0);
     }
 }
-
-#[derive(Debug, Clone, Copy)]
-pub struct ScanItem {
-    pub ssid: [u8; 32],
-    pub channel: u8,
-    pub rssi: i8,
-    pub bssid: [u8; 6],
-}
@@ -0,0 +1,174 @@
+use embedded_time::duration::Milliseconds;
+
+use crate::binary::wifi_mgmr_api;
+
+use super::{is_connected, WifiEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSaveMode {
+    Off,
+    /// Legacy power save, the AP buffers frames until we poll them
+    On,
+    /// Stays awake while there is traffic and dozes otherwise
+    Dynamic,
+}
+
+/// The shipped blob doesn't implement setting the listen interval and the
+/// active time, they have to be `None` until a blob which does is used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerSave {
+    pub mode: PowerSaveMode,
+    /// Number of beacon intervals between wake ups
+    pub listen_interval: Option<u16>,
+    /// How long to stay awake after waking up
+    pub active_time: Option<Milliseconds>,
+}
+
+const POWER_SAVE_DEFAULT: PowerSave = PowerSave {
+    mode: PowerSaveMode::Off,
+    listen_interval: None,
+    active_time: None,
+};
+
+impl Default for PowerSave {
+    fn default() -> Self {
+        POWER_SAVE_DEFAULT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSaveState {
+    Off,
+    /// Power save is configured and gets entered once we got an IP address
+    Pending,
+    Active(PowerSaveMode),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSaveError {
+    /// The blob doesn't support setting the listen interval or the active time
+    Unsupported,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+static mut POWER_SAVE: PowerSave = POWER_SAVE_DEFAULT;
+static mut POWER_SAVE_ACTIVE: bool = false;
+// set when we got an IP address, power save is entered from the network loop
+static mut ENTER_PENDING: bool = false;
+
+/// Configures station power save. It's applied immediately when connected,
+/// otherwise after the next connection got established.
+pub fn set_power_save(config: PowerSave) -> Result<(), PowerSaveError> {
+    validate(&config)?;
+
+    riscv::interrupt::free(|_| unsafe {
+        POWER_SAVE = config;
+    });
+
+    if config.mode == PowerSaveMode::Off {
+        return exit_power_save();
+    }
+
+    if is_connected() {
+        enter_power_save()?;
+    }
+
+    Ok(())
+}
+
+/// The configured power save settings
+pub fn power_save() -> PowerSave {
+    riscv::interrupt::free(|_| unsafe { POWER_SAVE })
+}
+
+pub fn power_save_state() -> PowerSaveState {
+    let (config, active) = riscv::interrupt::free(|_| unsafe { (POWER_SAVE, POWER_SAVE_ACTIVE) });
+
+    match (config.mode, active) {
+        (PowerSaveMode::Off, _) => PowerSaveState::Off,
+        (mode, true) => PowerSaveState::Active(mode),
+        (_, false) => PowerSaveState::Pending,
+    }
+}
+
+pub(crate) fn validate(config: &PowerSave) -> Result<(), PowerSaveError> {
+    if config.listen_interval.is_some() || config.active_time.is_some() {
+        return Err(PowerSaveError::Unsupported);
+    }
+
+    Ok(())
+}
+
+fn enter_power_save() -> Result<(), PowerSaveError> {
+    let config = power_save();
+
+    let ps_level = match config.mode {
+        PowerSaveMode::Off => return exit_power_save(),
+        PowerSaveMode::On => wifi_mgmr_api::PS_MODE_ON,
+        PowerSaveMode::Dynamic => wifi_mgmr_api::PS_MODE_ON_DYN,
+    };
+
+    let res = unsafe { wifi_mgmr_api::wifi_mgmr_sta_ps_enter(ps_level as u32) };
+    if res != 0 {
+        return Err(PowerSaveError::Failed(res));
+    }
+
+    riscv::interrupt::free(|_| unsafe {
+        POWER_SAVE_ACTIVE = true;
+    });
+
+    Ok(())
+}
+
+fn exit_power_save() -> Result<(), PowerSaveError> {
+    let active = riscv::interrupt::free(|_| unsafe { POWER_SAVE_ACTIVE });
+    if !active {
+        return Ok(());
+    }
+
+    let res = unsafe { wifi_mgmr_api::wifi_mgmr_sta_ps_exit() };
+
+    riscv::interrupt::free(|_| unsafe {
+        POWER_SAVE_ACTIVE = false;
+    });
+
+    if res != 0 {
+        return Err(PowerSaveError::Failed(res));
+    }
+
+    Ok(())
+}
+
+/// Power save ends with the connection
+pub(crate) fn reset_state() {
+    riscv::interrupt::free(|_| unsafe {
+        POWER_SAVE_ACTIVE = false;
+        ENTER_PENDING = false;
+    });
+}
+
+/// The blob leaves power save on its own when the connection is lost.
+/// Must be called with interrupts disabled.
+pub(crate) unsafe fn on_event(event: WifiEvent) {
+    if event == WifiEvent::GotIp {
+        ENTER_PENDING = true;
+    } else if event.is_disconnect() {
+        POWER_SAVE_ACTIVE = false;
+        ENTER_PENDING = false;
+    }
+}
+
+/// Enters the configured power save mode after we got an IP address.
+/// Called from the network loop, the blob can't be called from its event callback.
+pub(crate) fn poll() {
+    let pending = riscv::interrupt::free(|_| unsafe {
+        let pending = ENTER_PENDING;
+        ENTER_PENDING = false;
+        pending
+    });
+
+    if pending && power_save().mode != PowerSaveMode::Off && is_connected() {
+        enter_power_save().ok();
+    }
+}
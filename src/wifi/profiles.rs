@@ -0,0 +1,383 @@
+use embedded_time::duration::Milliseconds;
+
+use crate::compat::get_time;
+use crate::log;
+
+use super::connect::{ConnectHandle, SSID_MAX_LEN};
+use super::psk::{derive_psk, PSK_LEN};
+use super::{
+    connect_with_options_async, is_connected, scan_async, sta_down_requested, ConnectOptions,
+    ScanConfig, ScanHandle, ScanItem, SCAN_ITEMS_MAX,
+};
+
+/// Number of networks a [NetworkProfiles] store can hold
+pub const PROFILES_MAX: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileError {
+    /// The SSID is empty or longer than 32 bytes
+    InvalidSsid,
+    /// The passphrase is neither empty nor 8 to 63 printable ASCII characters
+    InvalidPassphrase,
+    /// All `PROFILES_MAX` slots are used
+    Full,
+}
+
+/// Credentials of a known network
+#[derive(Clone, Copy)]
+pub struct NetworkProfile {
+    ssid: [u8; SSID_MAX_LEN],
+    ssid_len: usize,
+    priority: u8,
+    /// `None` for open networks
+    psk: Option<[u8; PSK_LEN]>,
+}
+
+impl NetworkProfile {
+    pub fn ssid(&self) -> &[u8] {
+        &self.ssid[..self.ssid_len]
+    }
+
+    pub fn ssid_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(self.ssid()) }
+    }
+
+    /// Higher values are preferred
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProfileStatus {
+    /// Scanning for known networks
+    Scanning,
+    /// No known network was found, retrying after `retry_delay`
+    Waiting,
+    /// Connecting to the profile with the given index
+    Connecting {
+        index: usize,
+    },
+    Connected {
+        index: usize,
+    },
+    /// The station was taken down by `disconnect`, `idle` or `deinit`.
+    /// Resumes once a connection attempt is started.
+    Paused,
+}
+
+/// Strongest AP of a profile found by the last scan
+#[derive(Clone, Copy)]
+struct Candidate {
+    bssid: [u8; 6],
+    channel: u8,
+    rssi: i8,
+}
+
+enum State {
+    Waiting { until: u32 },
+    Scanning { handle: ScanHandle, until: u32 },
+    Connecting { index: usize, handle: ConnectHandle },
+    Connected { index: usize },
+    Paused,
+}
+
+/// Several networks with priorities. `poll` scans on boot and after the
+/// connection got lost and connects to the known network with the highest
+/// priority, the strongest signal decides between equal priorities.
+///
+/// The blob only keeps a single profile so the credentials are kept here.
+pub struct NetworkProfiles {
+    profiles: [Option<NetworkProfile>; PROFILES_MAX],
+    retry_delay: Milliseconds,
+    attempt_timeout: Milliseconds,
+    /// Profiles found by the last scan and not failed since
+    candidates: [Option<Candidate>; PROFILES_MAX],
+    state: State,
+}
+
+impl NetworkProfiles {
+    pub fn new() -> NetworkProfiles {
+        NetworkProfiles {
+            profiles: [None; PROFILES_MAX],
+            retry_delay: Milliseconds(10_000),
+            attempt_timeout: Milliseconds(15_000),
+            candidates: [None; PROFILES_MAX],
+            state: State::Waiting { until: 0 },
+        }
+    }
+
+    /// Delay before scanning again when no known network was found or connecting failed
+    pub fn set_retry_delay(&mut self, delay: Milliseconds) {
+        self.retry_delay = delay;
+    }
+
+    /// Timeout of a single connection attempt and of the scan before it
+    pub fn set_attempt_timeout(&mut self, timeout: Milliseconds) {
+        self.attempt_timeout = timeout;
+    }
+
+    /// Adds a network or updates the profile with the same SSID. Derives the
+    /// PSK right away, this takes a few seconds for secured networks.
+    /// Use an empty passphrase for open networks.
+    pub fn add(&mut self, ssid: &str, passphrase: &str, priority: u8) -> Result<(), ProfileError> {
+        if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+            return Err(ProfileError::InvalidSsid);
+        }
+
+        let psk = if passphrase.is_empty() {
+            None
+        } else {
+            Some(
+                derive_psk(passphrase, ssid.as_bytes())
+                    .map_err(|_| ProfileError::InvalidPassphrase)?,
+            )
+        };
+
+        let mut profile = NetworkProfile {
+            ssid: [0u8; SSID_MAX_LEN],
+            ssid_len: ssid.len(),
+            priority,
+            psk,
+        };
+        profile.ssid[..ssid.len()].copy_from_slice(ssid.as_bytes());
+
+        let slot = match self.position(ssid.as_bytes()) {
+            Some(index) => &mut self.profiles[index],
+            None => self
+                .profiles
+                .iter_mut()
+                .find(|slot| slot.is_none())
+                .ok_or(ProfileError::Full)?,
+        };
+        *slot = Some(profile);
+
+        Ok(())
+    }
+
+    /// Removes the profile of the SSID, returns false if it's unknown.
+    /// Looks for another network if it's the one in use.
+    pub fn remove(&mut self, ssid: &str) -> bool {
+        let index = match self.position(ssid.as_bytes()) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        self.profiles[index] = None;
+        self.candidates[index] = None;
+
+        let in_use = match self.state {
+            State::Connecting { index: active, .. } | State::Connected { index: active } => {
+                active == index
+            }
+            _ => false,
+        };
+        if in_use {
+            self.state = State::Waiting {
+                until: get_time().0,
+            };
+        }
+
+        true
+    }
+
+    pub fn get(&self, index: usize) -> Option<&NetworkProfile> {
+        self.profiles.get(index)?.as_ref()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NetworkProfile> {
+        self.profiles.iter().filter_map(|profile| profile.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The profile we are connected to
+    pub fn active(&self) -> Option<&NetworkProfile> {
+        match self.state {
+            State::Connected { index } => self.get(index),
+            _ => None,
+        }
+    }
+
+    /// Picks the profile to connect to from a scan result. Returns the index of
+    /// the profile, the BSSID and the channel.
+    pub fn select(
+        &self,
+        scan_result: &[Option<ScanItem>; SCAN_ITEMS_MAX],
+    ) -> Option<(usize, [u8; 6], u8)> {
+        self.best(&self.find_candidates(scan_result))
+    }
+
+    /// The strongest AP of every profile in the scan result
+    fn find_candidates(
+        &self,
+        scan_result: &[Option<ScanItem>; SCAN_ITEMS_MAX],
+    ) -> [Option<Candidate>; PROFILES_MAX] {
+        let mut candidates = [None; PROFILES_MAX];
+
+        for item in scan_result.iter().filter_map(|item| item.as_ref()) {
+            let index = match self.position(&item.ssid[..item.ssid_len]) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let stronger = match candidates[index] {
+                Some(Candidate { rssi, .. }) => item.rssi > rssi,
+                None => true,
+            };
+            if stronger {
+                candidates[index] = Some(Candidate {
+                    bssid: item.bssid,
+                    channel: item.channel,
+                    rssi: item.rssi,
+                });
+            }
+        }
+
+        candidates
+    }
+
+    fn best(&self, candidates: &[Option<Candidate>; PROFILES_MAX]) -> Option<(usize, [u8; 6], u8)> {
+        candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(index, candidate)| {
+                let candidate = candidate.as_ref()?;
+                let priority = self.profiles[index].as_ref()?.priority;
+                Some((priority, candidate.rssi, index, candidate))
+            })
+            .max_by_key(|(priority, rssi, ..)| (*priority, *rssi))
+            .map(|(_, _, index, candidate)| (index, candidate.bssid, candidate.channel))
+    }
+
+    pub fn status(&self) -> ProfileStatus {
+        match self.state {
+            State::Waiting { .. } => ProfileStatus::Waiting,
+            State::Scanning { .. } => ProfileStatus::Scanning,
+            State::Connecting { index, .. } => ProfileStatus::Connecting { index },
+            State::Connected { index } => ProfileStatus::Connected { index },
+            State::Paused => ProfileStatus::Paused,
+        }
+    }
+
+    /// Call this regularly e.g. from the network loop
+    pub fn poll(&mut self) -> ProfileStatus {
+        let now = get_time().0;
+
+        if sta_down_requested() {
+            self.state = State::Paused;
+            return self.status();
+        }
+
+        match &mut self.state {
+            State::Waiting { until } => {
+                if now.wrapping_sub(*until) as i32 >= 0 && !self.is_empty() {
+                    self.start_scan(now);
+                }
+            }
+            State::Scanning { handle, until } => match handle.poll() {
+                Err(nb::Error::WouldBlock) if now.wrapping_sub(*until) as i32 >= 0 => {
+                    log!("scan timed out");
+                    self.retry_later(now);
+                }
+                Ok(scan_result) => {
+                    self.candidates = self.find_candidates(&scan_result);
+                    self.next_candidate(now);
+                }
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(_err)) => {
+                    log!("scan failed {:?}", _err);
+                    self.retry_later(now);
+                }
+            },
+            State::Connecting { index, handle } => match handle.poll() {
+                Ok(()) => {
+                    self.state = State::Connected { index: *index };
+                }
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(_err)) => {
+                    log!("connecting failed {:?}", _err);
+                    // try the next known network of this scan
+                    self.candidates[*index] = None;
+                    self.next_candidate(now);
+                }
+            },
+            State::Connected { .. } => {
+                if !is_connected() {
+                    log!("connection lost, scanning");
+                    self.start_scan(now);
+                }
+            }
+            State::Paused => self.retry_later(now),
+        }
+
+        self.status()
+    }
+
+    fn position(&self, ssid: &[u8]) -> Option<usize> {
+        self.profiles.iter().position(|profile| match profile {
+            Some(profile) => profile.ssid() == ssid,
+            None => false,
+        })
+    }
+
+    fn start_scan(&mut self, now: u32) {
+        match scan_async(&ScanConfig::default()) {
+            Ok(handle) => {
+                self.state = State::Scanning {
+                    handle,
+                    until: now.wrapping_add(self.attempt_timeout.0),
+                }
+            }
+            Err(_) => self.retry_later(now),
+        }
+    }
+
+    fn next_candidate(&mut self, now: u32) {
+        match self.best(&self.candidates) {
+            Some((index, bssid, channel)) => self.start_attempt(now, index, bssid, channel),
+            None => {
+                log!("no known network found");
+                self.retry_later(now);
+            }
+        }
+    }
+
+    fn start_attempt(&mut self, now: u32, index: usize, bssid: [u8; 6], channel: u8) {
+        let profile = match self.profiles[index].as_ref() {
+            Some(profile) => profile,
+            None => return self.retry_later(now),
+        };
+
+        let options = ConnectOptions {
+            bssid: Some(bssid),
+            channel: Some(channel),
+            psk: profile.psk,
+            timeout: Some(self.attempt_timeout),
+            ..ConnectOptions::default()
+        };
+
+        match connect_with_options_async(profile.ssid_str(), "", &options) {
+            Ok(handle) => self.state = State::Connecting { index, handle },
+            Err(_) => self.retry_later(now),
+        }
+    }
+
+    fn retry_later(&mut self, now: u32) {
+        self.state = State::Waiting {
+            until: now.wrapping_add(self.retry_delay.0),
+        };
+    }
+}
+
+impl Default for NetworkProfiles {
+    fn default() -> Self {
+        NetworkProfiles::new()
+    }
+}
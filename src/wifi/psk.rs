@@ -0,0 +1,318 @@
+//! WPA2 PSK derivation (PBKDF2-HMAC-SHA1 with 4096 iterations).
+//!
+//! Deriving the PSK is slow, derive it once and pass it via
+//! `ConnectOptions::psk` on every connect. This doesn't depend on the blob.
+
+pub const PSK_LEN: usize = 32;
+
+const ITERATIONS: u32 = 4096;
+const SHA1_BLOCK_LEN: usize = 64;
+const SHA1_DIGEST_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+pub enum PskError {
+    /// The passphrase must be 8 to 63 printable ASCII characters
+    InvalidPassphrase,
+    /// The SSID is empty or longer than 32 bytes
+    InvalidSsid,
+    /// Not exactly 64 hex digits
+    InvalidHex,
+}
+
+/// Derives the PSK (PMK) of a WPA2 personal network
+pub fn derive_psk(passphrase: &str, ssid: &[u8]) -> Result<[u8; PSK_LEN], PskError> {
+    if passphrase.len() < 8
+        || passphrase.len() > 63
+        || passphrase.bytes().any(|b| !(0x20..=0x7e).contains(&b))
+    {
+        return Err(PskError::InvalidPassphrase);
+    }
+
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err(PskError::InvalidSsid);
+    }
+
+    let mut psk = [0u8; PSK_LEN];
+    pbkdf2_hmac_sha1(passphrase.as_bytes(), ssid, ITERATIONS, &mut psk);
+    Ok(psk)
+}
+
+/// The PSK as 64 lowercase hex digits
+pub fn psk_to_hex(psk: &[u8; PSK_LEN]) -> [u8; PSK_LEN * 2] {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut hex = [0u8; PSK_LEN * 2];
+    for (byte, chunk) in psk.iter().zip(hex.chunks_mut(2)) {
+        chunk[0] = HEX[(*byte >> 4) as usize];
+        chunk[1] = HEX[(*byte & 0xf) as usize];
+    }
+    hex
+}
+
+pub fn psk_from_hex(hex: &str) -> Result<[u8; PSK_LEN], PskError> {
+    if hex.len() != PSK_LEN * 2 {
+        return Err(PskError::InvalidHex);
+    }
+
+    let mut psk = [0u8; PSK_LEN];
+    for (byte, chunk) in psk.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let high = (chunk[0] as char)
+            .to_digit(16)
+            .ok_or(PskError::InvalidHex)?;
+        let low = (chunk[1] as char)
+            .to_digit(16)
+            .ok_or(PskError::InvalidHex)?;
+        *byte = ((high << 4) | low) as u8;
+    }
+    Ok(psk)
+}
+
+fn pbkdf2_hmac_sha1(password: &[u8], salt: &[u8], iterations: u32, out: &mut [u8]) {
+    let hmac = Hmac::new(password);
+
+    for (index, chunk) in out.chunks_mut(SHA1_DIGEST_LEN).enumerate() {
+        let mut block_index = [0u8; 4];
+        block_index.copy_from_slice(&(index as u32 + 1).to_be_bytes());
+
+        let mut u = hmac.mac(&[salt, &block_index]);
+        let mut t = u;
+        for _ in 1..iterations {
+            u = hmac.mac(&[&u]);
+            for (t, u) in t.iter_mut().zip(u.iter()) {
+                *t ^= *u;
+            }
+        }
+
+        chunk.copy_from_slice(&t[..chunk.len()]);
+    }
+}
+
+/// HMAC-SHA1 with the inner and outer key pads already hashed
+struct Hmac {
+    inner: Sha1,
+    outer: Sha1,
+}
+
+impl Hmac {
+    fn new(key: &[u8]) -> Hmac {
+        let mut key_block = [0u8; SHA1_BLOCK_LEN];
+        if key.len() > SHA1_BLOCK_LEN {
+            let mut sha1 = Sha1::new();
+            sha1.update(key);
+            key_block[..SHA1_DIGEST_LEN].copy_from_slice(&sha1.finish());
+        } else {
+            key_block[..key.len()].copy_from_slice(key);
+        }
+
+        let mut inner_pad = [0x36u8; SHA1_BLOCK_LEN];
+        let mut outer_pad = [0x5cu8; SHA1_BLOCK_LEN];
+        for ((inner, outer), key) in inner_pad
+            .iter_mut()
+            .zip(outer_pad.iter_mut())
+            .zip(key_block.iter())
+        {
+            *inner ^= *key;
+            *outer ^= *key;
+        }
+
+        let mut inner = Sha1::new();
+        inner.update(&inner_pad);
+        let mut outer = Sha1::new();
+        outer.update(&outer_pad);
+
+        Hmac { inner, outer }
+    }
+
+    fn mac(&self, message: &[&[u8]]) -> [u8; SHA1_DIGEST_LEN] {
+        let mut inner = self.inner.clone();
+        for part in message {
+            inner.update(part);
+        }
+
+        let mut outer = self.outer.clone();
+        outer.update(&inner.finish());
+        outer.finish()
+    }
+}
+
+#[derive(Clone)]
+struct Sha1 {
+    state: [u32; 5],
+    block: [u8; SHA1_BLOCK_LEN],
+    block_len: usize,
+    total_len: u64,
+}
+
+impl Sha1 {
+    fn new() -> Sha1 {
+        Sha1 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0],
+            block: [0u8; SHA1_BLOCK_LEN],
+            block_len: 0,
+            total_len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        while !data.is_empty() {
+            let take = (SHA1_BLOCK_LEN - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+
+            if self.block_len == SHA1_BLOCK_LEN {
+                self.compress();
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish(mut self) -> [u8; SHA1_DIGEST_LEN] {
+        let bit_len = self.total_len * 8;
+
+        self.block[self.block_len] = 0x80;
+        self.block_len += 1;
+        if self.block_len > SHA1_BLOCK_LEN - 8 {
+            self.block[self.block_len..].fill(0);
+            self.compress();
+            self.block_len = 0;
+        }
+        self.block[self.block_len..SHA1_BLOCK_LEN - 8].fill(0);
+        self.block[SHA1_BLOCK_LEN - 8..].copy_from_slice(&bit_len.to_be_bytes());
+        self.compress();
+
+        let mut digest = [0u8; SHA1_DIGEST_LEN];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 80];
+        for (i, chunk) in self.block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = self.state;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5a827999),
+                20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        self.state[0] = self.state[0].wrapping_add(a);
+        self.state[1] = self.state[1].wrapping_add(b);
+        self.state[2] = self.state[2].wrapping_add(c);
+        self.state[3] = self.state[3].wrapping_add(d);
+        self.state[4] = self.state[4].wrapping_add(e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_psk(passphrase: &str, ssid: &str, expected: &str) {
+        let psk = derive_psk(passphrase, ssid.as_bytes()).unwrap();
+        assert_eq!(&psk_to_hex(&psk)[..], expected.as_bytes());
+    }
+
+    // IEEE 802.11i-2004 H.4.2
+    #[test]
+    fn test_vectors() {
+        assert_psk(
+            "password",
+            "IEEE",
+            "f42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e",
+        );
+        assert_psk(
+            "ThisIsAPassword",
+            "ThisIsASSID",
+            "0dc0d6eb90555ed6419756b9a15ec3e3209b63df707dd508d14581f8982721af",
+        );
+        assert_psk(
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "ZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZ",
+            "becb93866bb8c3832cb777c2f559807c8c59afcb6eae734885001300a981cc62",
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(
+            derive_psk("short", b"IEEE"),
+            Err(PskError::InvalidPassphrase)
+        );
+        assert_eq!(
+            derive_psk(core::str::from_utf8(&[b'a'; 64]).unwrap(), b"IEEE"),
+            Err(PskError::InvalidPassphrase)
+        );
+        assert_eq!(
+            derive_psk("pass\tword", b"IEEE"),
+            Err(PskError::InvalidPassphrase)
+        );
+        assert_eq!(derive_psk("password", b""), Err(PskError::InvalidSsid));
+        assert_eq!(
+            derive_psk("password", &[b'a'; 33]),
+            Err(PskError::InvalidSsid)
+        );
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let mut psk = [0u8; PSK_LEN];
+        for (i, byte) in psk.iter_mut().enumerate() {
+            *byte = (i * 37) as u8;
+        }
+
+        let hex = psk_to_hex(&psk);
+        assert_eq!(psk_from_hex(core::str::from_utf8(&hex).unwrap()), Ok(psk));
+
+        let upper = "F42C6FC52DF0EBEF9EBB4B90B38A5F902E83FE1B135A70E23AED762E9710A12E";
+        assert_eq!(
+            psk_from_hex(upper).map(|psk| psk_to_hex(&psk)),
+            Ok(*b"f42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e")
+        );
+    }
+
+    #[test]
+    fn invalid_hex() {
+        let valid = "f42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e";
+        assert_eq!(psk_from_hex(""), Err(PskError::InvalidHex));
+        assert_eq!(psk_from_hex(&valid[..63]), Err(PskError::InvalidHex));
+        assert_eq!(
+            psk_from_hex("f42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12eff"),
+            Err(PskError::InvalidHex)
+        );
+        assert_eq!(
+            psk_from_hex("g42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e"),
+            Err(PskError::InvalidHex)
+        );
+        assert_eq!(
+            psk_from_hex("+42c6fc52df0ebef9ebb4b90b38a5f902e83fe1b135a70e23aed762e9710a12e"),
+            Err(PskError::InvalidHex)
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use crate::binary::wifi_mgmr;
+
+use super::ieee80211::MANAGEMENT_HEADER_LEN;
+
+/// Longest frame `send_raw_80211` accepts
+pub const RAW_FRAME_MAX_LEN: usize = 1500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawSendError {
+    /// The frame is shorter than a MAC header or longer than `RAW_FRAME_MAX_LEN`
+    InvalidLength,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+/// Transmits a complete 802.11 frame (without FCS) on the current channel.
+/// Use [super::ieee80211::FrameWriter] to build the frame.
+pub fn send_raw_80211(frame: &[u8]) -> Result<(), RawSendError> {
+    if frame.len() < MANAGEMENT_HEADER_LEN || frame.len() > RAW_FRAME_MAX_LEN {
+        return Err(RawSendError::InvalidLength);
+    }
+
+    // the blob wants a mutable buffer
+    let mut buffer = [0u8; RAW_FRAME_MAX_LEN];
+    buffer[..frame.len()].copy_from_slice(frame);
+
+    let res =
+        unsafe { wifi_mgmr::wifi_mgmr_raw_80211_send(&mut buffer as *mut u8, frame.len() as i32) };
+    if res != 0 {
+        return Err(RawSendError::Failed(res));
+    }
+
+    Ok(())
+}
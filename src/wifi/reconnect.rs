@@ -0,0 +1,281 @@
+use embedded_time::duration::Milliseconds;
+
+use crate::compat::get_time;
+use crate::log;
+
+use super::connect::{ConnectHandle, SSID_MAX_LEN};
+use super::psk::{derive_psk, PSK_LEN};
+use super::{
+    connect_with_options_async, disconnect_count, is_connected, scan_async, sta_down_requested,
+    ConnectError, ConnectOptions, ScanConfig, ScanError, ScanHandle,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectConfig {
+    /// Delay before the first attempt after the connection got lost
+    pub initial_backoff: Milliseconds,
+    /// The delay doubles after every failed attempt up to this value
+    pub max_backoff: Milliseconds,
+    /// Give up after this many failed attempts, `None` retries forever
+    pub max_attempts: Option<u32>,
+    /// Timeout of a single connection attempt and of the scan before it
+    pub attempt_timeout: Milliseconds,
+    /// Rescan after a failed attempt and connect to the strongest BSSID of the SSID
+    pub rescan: bool,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig {
+            initial_backoff: Milliseconds(1_000),
+            max_backoff: Milliseconds(60_000),
+            max_attempts: None,
+            attempt_timeout: Milliseconds(15_000),
+            rescan: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReconnectStatus {
+    Connected,
+    /// Waiting for the backoff delay to expire
+    Waiting {
+        attempts: u32,
+    },
+    /// Looking for the strongest BSSID of the SSID before connecting
+    Scanning {
+        attempts: u32,
+    },
+    Connecting {
+        attempts: u32,
+    },
+    /// `max_attempts` exceeded - call `reset` to start over
+    GaveUp,
+    /// The station was taken down by `disconnect`, `idle` or `deinit`.
+    /// Resumes once a connection attempt is started.
+    Paused,
+}
+
+// `disconnects` is the last seen `disconnect_count()`
+enum State {
+    Monitoring { disconnects: u32 },
+    Waiting { until: u32 },
+    Scanning { handle: ScanHandle, until: u32 },
+    Connecting(ConnectHandle),
+    GaveUp,
+    Paused,
+}
+
+/// Watches the station connection and reconnects after it got lost.
+/// Call `poll` regularly e.g. from the network loop.
+pub struct ReconnectSupervisor {
+    ssid: [u8; SSID_MAX_LEN],
+    ssid_len: usize,
+    /// `None` for open networks
+    psk: Option<[u8; PSK_LEN]>,
+    config: ReconnectConfig,
+    state: State,
+    attempts: u32,
+}
+
+impl ReconnectSupervisor {
+    /// Derives the PSK right away, this takes a few seconds for secured networks.
+    /// Use an empty passphrase for open networks.
+    pub fn new(
+        ssid: &str,
+        passphrase: &str,
+        config: ReconnectConfig,
+    ) -> Result<ReconnectSupervisor, ConnectError> {
+        if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+            return Err(ConnectError::InvalidSsid);
+        }
+
+        let psk = if passphrase.is_empty() {
+            None
+        } else {
+            Some(
+                derive_psk(passphrase, ssid.as_bytes())
+                    .map_err(|_| ConnectError::InvalidPassphrase)?,
+            )
+        };
+
+        let mut supervisor = ReconnectSupervisor {
+            ssid: [0u8; SSID_MAX_LEN],
+            ssid_len: ssid.len(),
+            psk,
+            config,
+            state: State::Monitoring {
+                disconnects: disconnect_count(),
+            },
+            attempts: 0,
+        };
+        supervisor.ssid[..ssid.len()].copy_from_slice(ssid.as_bytes());
+
+        Ok(supervisor)
+    }
+
+    pub fn status(&self) -> ReconnectStatus {
+        match self.state {
+            State::Monitoring { .. } => ReconnectStatus::Connected,
+            State::Waiting { .. } => ReconnectStatus::Waiting {
+                attempts: self.attempts,
+            },
+            State::Scanning { .. } => ReconnectStatus::Scanning {
+                attempts: self.attempts,
+            },
+            State::Connecting(_) => ReconnectStatus::Connecting {
+                attempts: self.attempts,
+            },
+            State::GaveUp => ReconnectStatus::GaveUp,
+            State::Paused => ReconnectStatus::Paused,
+        }
+    }
+
+    /// Starts over after the supervisor gave up
+    pub fn reset(&mut self) {
+        self.monitor();
+    }
+
+    pub fn poll(&mut self) -> ReconnectStatus {
+        let now = get_time().0;
+
+        if sta_down_requested() {
+            self.state = State::Paused;
+            return self.status();
+        }
+
+        match &mut self.state {
+            State::Monitoring { disconnects } => {
+                let count = disconnect_count();
+                let bounced = count != *disconnects;
+                *disconnects = count;
+
+                if !is_connected() {
+                    log!("connection lost, reconnecting");
+                    self.attempts = 0;
+                    self.state = State::Waiting {
+                        until: now.wrapping_add(self.config.initial_backoff.0),
+                    };
+                } else if bounced {
+                    log!("connection lost and restored in between");
+                    self.attempts = 0;
+                }
+            }
+            State::Waiting { until } => {
+                if is_connected() {
+                    // the blob reconnected on its own
+                    self.monitor();
+                } else if now.wrapping_sub(*until) as i32 >= 0 {
+                    self.start_attempt(now);
+                }
+            }
+            State::Scanning { handle, until } => match handle.poll() {
+                Err(nb::Error::WouldBlock) if now.wrapping_sub(*until) as i32 >= 0 => {
+                    log!("scan timed out");
+                    self.attempt_failed(now);
+                }
+                Ok(scan_result) => {
+                    let strongest = scan_result
+                        .iter()
+                        .filter_map(|item| item.as_ref())
+                        .max_by_key(|item| item.rssi)
+                        .map(|item| (item.bssid, item.channel));
+                    self.connect(now, strongest);
+                }
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(ScanError::Timeout)) => {
+                    log!("scan timed out");
+                    self.attempt_failed(now);
+                }
+                Err(nb::Error::Other(_err)) => {
+                    log!("scan failed {:?}", _err);
+                    self.connect(now, None);
+                }
+            },
+            State::Connecting(handle) => match handle.poll() {
+                Ok(()) => self.monitor(),
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(_err)) => {
+                    log!("reconnect failed {:?}", _err);
+                    self.attempt_failed(now);
+                }
+            },
+            State::GaveUp => (),
+            State::Paused => self.monitor(),
+        }
+
+        self.status()
+    }
+
+    fn ssid_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(&self.ssid[..self.ssid_len]) }
+    }
+
+    fn monitor(&mut self) {
+        self.attempts = 0;
+        self.state = State::Monitoring {
+            disconnects: disconnect_count(),
+        };
+    }
+
+    fn start_attempt(&mut self, now: u32) {
+        self.attempts += 1;
+
+        if self.config.rescan && self.attempts > 1 {
+            let config = ScanConfig {
+                ssid: Some(self.ssid_str()),
+                ..ScanConfig::default()
+            };
+
+            match scan_async(&config) {
+                Ok(handle) => {
+                    self.state = State::Scanning {
+                        handle,
+                        until: now.wrapping_add(self.config.attempt_timeout.0),
+                    }
+                }
+                Err(_) => self.connect(now, None),
+            }
+        } else {
+            self.connect(now, None);
+        }
+    }
+
+    /// Connects to the given BSSID and channel or lets the blob pick one
+    fn connect(&mut self, now: u32, bssid: Option<([u8; 6], u8)>) {
+        let options = ConnectOptions {
+            bssid: bssid.map(|(bssid, _)| bssid),
+            channel: bssid.map(|(_, channel)| channel),
+            psk: self.psk,
+            timeout: Some(self.config.attempt_timeout),
+            ..ConnectOptions::default()
+        };
+
+        match connect_with_options_async(self.ssid_str(), "", &options) {
+            Ok(handle) => self.state = State::Connecting(handle),
+            Err(_) => self.attempt_failed(now),
+        }
+    }
+
+    fn attempt_failed(&mut self, now: u32) {
+        if let Some(max_attempts) = self.config.max_attempts {
+            if self.attempts >= max_attempts {
+                self.state = State::GaveUp;
+                return;
+            }
+        }
+
+        let shift = (self.attempts.max(1) - 1).min(16);
+        let backoff = self
+            .config
+            .initial_backoff
+            .0
+            .saturating_mul(1 << shift)
+            .min(self.config.max_backoff.0);
+
+        self.state = State::Waiting {
+            until: now.wrapping_add(backoff),
+        };
+    }
+}
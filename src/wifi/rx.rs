@@ -0,0 +1,112 @@
+use super::bl_free_rx_buffer;
+
+pub const RX_QUEUE_SIZE_MAX: usize = 32;
+pub const RX_QUEUE_SIZE_DEFAULT: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DataFrame {
+    pub len: usize,
+    pub data: *mut u8,
+}
+
+/// Counters of the RX path. Every frame handed to us by the blob is either
+/// dropped or consumed - in both cases it gets freed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RxStats {
+    pub received: u32,
+    pub dropped: u32,
+    pub freed: u32,
+}
+
+/// Bounded queue of frames received from the blob.
+/// All methods must be called with interrupts disabled.
+pub(crate) struct RxQueue {
+    frames: [Option<DataFrame>; RX_QUEUE_SIZE_MAX],
+    read_index: usize,
+    len: usize,
+    depth: usize,
+    stats: RxStats,
+}
+
+impl RxQueue {
+    pub const fn new() -> RxQueue {
+        RxQueue {
+            frames: [None; RX_QUEUE_SIZE_MAX],
+            read_index: 0,
+            len: 0,
+            depth: RX_QUEUE_SIZE_DEFAULT,
+            stats: RxStats {
+                received: 0,
+                dropped: 0,
+                freed: 0,
+            },
+        }
+    }
+
+    /// Takes ownership of the frame. If the queue is full the frame is
+    /// dropped and given back to the blob immediately.
+    pub unsafe fn enqueue(&mut self, frame: DataFrame) -> bool {
+        self.stats.received = self.stats.received.wrapping_add(1);
+
+        if self.len >= self.depth {
+            self.stats.dropped = self.stats.dropped.wrapping_add(1);
+            self.free(frame);
+            return false;
+        }
+
+        let write_index = (self.read_index + self.len) % RX_QUEUE_SIZE_MAX;
+        self.frames[write_index] = Some(frame);
+        self.len += 1;
+        true
+    }
+
+    pub fn dequeue(&mut self) -> Option<DataFrame> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let frame = self.frames[self.read_index].take();
+        self.read_index = (self.read_index + 1) % RX_QUEUE_SIZE_MAX;
+        self.len -= 1;
+        frame
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Changes the number of frames the queue will hold. Frames exceeding the
+    /// new depth are dropped.
+    pub unsafe fn set_depth(&mut self, depth: usize) {
+        self.depth = depth.max(1).min(RX_QUEUE_SIZE_MAX);
+
+        while self.len > self.depth {
+            let write_index = (self.read_index + self.len - 1) % RX_QUEUE_SIZE_MAX;
+            if let Some(frame) = self.frames[write_index].take() {
+                self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                self.free(frame);
+            }
+            self.len -= 1;
+        }
+    }
+
+    /// Gives all queued frames back to the blob
+    pub unsafe fn flush(&mut self) {
+        while let Some(frame) = self.dequeue() {
+            self.free(frame);
+        }
+    }
+
+    pub unsafe fn free(&mut self, frame: DataFrame) {
+        bl_free_rx_buffer(frame.data);
+        self.stats.freed = self.stats.freed.wrapping_add(1);
+    }
+
+    pub fn stats(&self) -> RxStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = RxStats::default();
+    }
+}
@@ -0,0 +1,301 @@
+use embedded_time::duration::Milliseconds;
+
+use crate::binary::c_types::c_void;
+use crate::binary::wifi_mgmr;
+use crate::compat::get_time;
+
+use super::connect::SSID_MAX_LEN;
+use super::{regulatory_domain, wifiMgmr};
+
+/// Number of scan results the blob keeps
+pub const SCAN_ITEMS_MAX: usize = 50;
+/// A scan the blob didn't report back within this time is given up
+pub const SCAN_TIMEOUT: Milliseconds = Milliseconds(10_000);
+
+static mut SCAN_IN_PROGRESS: bool = false;
+// counts started scans so a handle only gives up its own scan
+static mut SCAN_ID: u32 = 0;
+static mut LAST_SCAN_RESULT: [Option<ScanItem>; SCAN_ITEMS_MAX] = [None; SCAN_ITEMS_MAX];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthMode {
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa2Psk,
+    WpaWpa2Psk,
+    WpaEnterprise,
+    Wpa3Sae,
+    Wpa2PskWpa3Sae,
+    Unknown(u8),
+}
+
+impl AuthMode {
+    pub fn from_raw(auth: u8) -> AuthMode {
+        match auth {
+            0 => AuthMode::Open,
+            1 => AuthMode::Wep,
+            2 => AuthMode::WpaPsk,
+            3 => AuthMode::Wpa2Psk,
+            4 => AuthMode::WpaWpa2Psk,
+            5 => AuthMode::WpaEnterprise,
+            6 => AuthMode::Wpa3Sae,
+            7 => AuthMode::Wpa2PskWpa3Sae,
+            other => AuthMode::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cipher {
+    None,
+    Wep,
+    Aes,
+    Tkip,
+    TkipAes,
+    Unknown(u8),
+}
+
+impl Cipher {
+    pub fn from_raw(cipher: u8) -> Cipher {
+        match cipher {
+            0 => Cipher::None,
+            1 => Cipher::Wep,
+            2 => Cipher::Aes,
+            3 => Cipher::Tkip,
+            4 => Cipher::TkipAes,
+            other => Cipher::Unknown(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScanItem {
+    pub ssid: [u8; 32],
+    pub ssid_len: usize,
+    pub channel: u8,
+    pub rssi: i8,
+    pub bssid: [u8; 6],
+    pub auth: AuthMode,
+    pub cipher: Cipher,
+    pub ppm_abs: i8,
+    pub ppm_rel: i8,
+    /// Time in ms (blob's clock) the AP was seen last
+    pub timestamp_lastseen: u32,
+}
+
+impl ScanItem {
+    /// The SSID, empty for hidden networks or if it isn't valid UTF-8
+    pub fn ssid_str(&self) -> &str {
+        core::str::from_utf8(&self.ssid[..self.ssid_len]).unwrap_or("")
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.ssid_len == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScanConfig<'a> {
+    /// Channels to scan, all channels of the regulatory domain if empty
+    pub channels: &'a [u8],
+    /// Only report APs with this SSID, also finds the AP if it's hidden
+    pub ssid: Option<&'a str>,
+    /// Don't report APs which hide their SSID
+    pub filter_hidden: bool,
+}
+
+impl<'a> Default for ScanConfig<'a> {
+    fn default() -> Self {
+        ScanConfig {
+            channels: &[],
+            ssid: None,
+            filter_hidden: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScanError {
+    /// Another scan is in progress
+    Busy,
+    /// A channel isn't allowed in the regulatory domain
+    InvalidChannel,
+    /// The SSID filter is empty or longer than 32 bytes
+    InvalidSsid,
+    /// The blob refused to start the scan
+    Failed(i32),
+    /// The blob didn't report the result within `SCAN_TIMEOUT`
+    Timeout,
+}
+
+/// A scan started by [scan_async]. Dropping the handle before the scan is done
+/// gives it up, a late result just updates the stored result.
+pub struct ScanHandle {
+    ssid: [u8; SSID_MAX_LEN],
+    ssid_len: usize,
+    channel_mask: u16,
+    started: Milliseconds,
+    id: u32,
+}
+
+impl ScanHandle {
+    /// Returns `WouldBlock` until the scan is done
+    pub fn poll(&mut self) -> nb::Result<[Option<ScanItem>; SCAN_ITEMS_MAX], ScanError> {
+        let done = riscv::interrupt::free(|_| unsafe { !SCAN_IN_PROGRESS });
+        if !done {
+            if get_time().0.wrapping_sub(self.started.0) >= SCAN_TIMEOUT.0 {
+                riscv::interrupt::free(|_| unsafe {
+                    SCAN_IN_PROGRESS = false;
+                });
+                return Err(nb::Error::Other(ScanError::Timeout));
+            }
+
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let mut result = riscv::interrupt::free(|_| unsafe { LAST_SCAN_RESULT });
+
+        // the blob keeps results of earlier scans for a while
+        for entry in result.iter_mut() {
+            let keep = match entry {
+                Some(item) => {
+                    self.channel_mask & (1 << item.channel) != 0
+                        && (self.ssid_len == 0
+                            || &item.ssid[..item.ssid_len] == &self.ssid[..self.ssid_len])
+                }
+                None => false,
+            };
+
+            if !keep {
+                *entry = None;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl Drop for ScanHandle {
+    fn drop(&mut self) {
+        riscv::interrupt::free(|_| unsafe {
+            if SCAN_ID == self.id {
+                SCAN_IN_PROGRESS = false;
+            }
+        });
+    }
+}
+
+/// Scans for APs and waits for the result
+pub fn scan(config: &ScanConfig) -> Result<[Option<ScanItem>; SCAN_ITEMS_MAX], ScanError> {
+    let mut handle = scan_async(config)?;
+    nb::block!(handle.poll())
+}
+
+/// Starts a scan. The returned handle can be polled e.g. from the network loop.
+pub fn scan_async(config: &ScanConfig) -> Result<ScanHandle, ScanError> {
+    let domain = regulatory_domain();
+    let channels = if config.channels.is_empty() {
+        domain.channels()
+    } else {
+        config.channels
+    };
+
+    if channels.len() > wifi_mgmr::MAX_FIXED_CHANNELS_LIMIT as usize
+        || channels
+            .iter()
+            .any(|channel| !domain.allows_channel(*channel))
+    {
+        return Err(ScanError::InvalidChannel);
+    }
+
+    let mut ssid_buf = [0u8; SSID_MAX_LEN + 1];
+    if let Some(ssid) = config.ssid {
+        if ssid.is_empty() || ssid.len() > SSID_MAX_LEN {
+            return Err(ScanError::InvalidSsid);
+        }
+        ssid_buf[..ssid.len()].copy_from_slice(ssid.as_bytes());
+    }
+
+    let mut channel_list = [0u16; wifi_mgmr::MAX_FIXED_CHANNELS_LIMIT as usize];
+    for (entry, channel) in channel_list.iter_mut().zip(channels.iter()) {
+        *entry = *channel as u16;
+    }
+
+    let channel_mask = channels
+        .iter()
+        .fold(0u16, |mask, channel| mask | (1 << *channel));
+
+    let id = riscv::interrupt::free(|_| unsafe {
+        if SCAN_IN_PROGRESS {
+            None
+        } else {
+            SCAN_IN_PROGRESS = true;
+            SCAN_ID = SCAN_ID.wrapping_add(1);
+            Some(SCAN_ID)
+        }
+    });
+    let id = id.ok_or(ScanError::Busy)?;
+
+    let res = unsafe {
+        wifi_mgmr::wifi_mgmr_scan_filter_hidden_ssid(config.filter_hidden as i32);
+
+        if config.ssid.is_none() && config.channels.is_empty() {
+            wifi_mgmr::wifi_mgmr_scan(core::ptr::null_mut::<c_void>(), Some(scan_cb))
+        } else {
+            // an empty SSID doesn't filter
+            wifi_mgmr::wifi_mgmr_scan_adv(
+                core::ptr::null_mut::<c_void>(),
+                Some(scan_cb),
+                &mut channel_list as *mut u16,
+                channels.len() as u16,
+                &ssid_buf as *const u8,
+            )
+        }
+    };
+
+    if res != 0 {
+        riscv::interrupt::free(|_| unsafe {
+            SCAN_IN_PROGRESS = false;
+        });
+        return Err(ScanError::Failed(res));
+    }
+
+    let mut handle = ScanHandle {
+        ssid: [0u8; SSID_MAX_LEN],
+        ssid_len: 0,
+        channel_mask,
+        started: get_time(),
+        id,
+    };
+    if let Some(ssid) = config.ssid {
+        handle.ssid[..ssid.len()].copy_from_slice(ssid.as_bytes());
+        handle.ssid_len = ssid.len();
+    }
+
+    Ok(handle)
+}
+
+unsafe extern "C" fn scan_cb(_data: *mut c_void, _param: *mut c_void) {
+    for i in 0..SCAN_ITEMS_MAX {
+        let item = wifiMgmr.scan_items[i];
+        if item.is_used != 0 {
+            LAST_SCAN_RESULT[i] = Some(ScanItem {
+                ssid: item.ssid,
+                ssid_len: (item.ssid_len as usize).min(SSID_MAX_LEN),
+                channel: item.channel,
+                rssi: item.rssi,
+                bssid: item.bssid,
+                auth: AuthMode::from_raw(item.auth),
+                cipher: Cipher::from_raw(item.cipher),
+                ppm_abs: item.ppm_abs,
+                ppm_rel: item.ppm_rel,
+                timestamp_lastseen: item.timestamp_lastseen,
+            });
+        } else {
+            LAST_SCAN_RESULT[i] = None;
+        }
+    }
+
+    SCAN_IN_PROGRESS = false;
+}
@@ -0,0 +1,117 @@
+use crate::binary::c_types::c_void;
+use crate::binary::wifi_mgmr;
+
+use super::regulatory_domain;
+
+static mut SNIFFER_ACTIVE: bool = false;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnifferError {
+    /// Another sniffer is running
+    AlreadyStarted,
+    InvalidChannel,
+    /// The blob returned an error
+    Failed(i32),
+}
+
+/// Receives every frame on the current channel while it's alive. Dropping
+/// it unregisters the callback and leaves sniffer mode.
+///
+/// Use [super::ieee80211::FrameHeader] to look into the frames.
+pub struct Sniffer<F: FnMut(&[u8]) + 'static> {
+    callback: *mut F,
+}
+
+impl<F: FnMut(&[u8]) + 'static> Sniffer<F> {
+    /// Switches to sniffer mode on the given channel. The callback gets called
+    /// from the context of the wifi tasks so it should return quickly.
+    pub fn start(channel: u8, callback: &'static mut F) -> Result<Sniffer<F>, SnifferError> {
+        if !regulatory_domain().allows_channel(channel) {
+            return Err(SnifferError::InvalidChannel);
+        }
+
+        let active = riscv::interrupt::free(|_| unsafe {
+            if SNIFFER_ACTIVE {
+                true
+            } else {
+                SNIFFER_ACTIVE = true;
+                false
+            }
+        });
+        if active {
+            return Err(SnifferError::AlreadyStarted);
+        }
+
+        let sniffer = Sniffer {
+            callback: callback as *mut F,
+        };
+
+        let res = unsafe { wifi_mgmr::wifi_mgmr_sniffer_enable() };
+        if res != 0 {
+            riscv::interrupt::free(|_| unsafe {
+                SNIFFER_ACTIVE = false;
+            });
+            core::mem::forget(sniffer);
+            return Err(SnifferError::Failed(res));
+        }
+
+        // from here on dropping the sniffer cleans up
+        sniffer.set_channel(channel)?;
+
+        let res = unsafe {
+            wifi_mgmr::wifi_mgmr_sniffer_register(
+                sniffer.callback as *mut c_void,
+                Some(trampoline::<F>),
+            )
+        };
+        if res != 0 {
+            return Err(SnifferError::Failed(res));
+        }
+
+        Ok(sniffer)
+    }
+
+    /// Changes the channel e.g. to hop channels during a site survey
+    pub fn set_channel(&self, channel: u8) -> Result<(), SnifferError> {
+        if !regulatory_domain().allows_channel(channel) {
+            return Err(SnifferError::InvalidChannel);
+        }
+
+        let res = unsafe { wifi_mgmr::wifi_mgmr_channel_set(channel as i32, 0) };
+        if res != 0 {
+            return Err(SnifferError::Failed(res));
+        }
+
+        Ok(())
+    }
+
+    pub fn channel(&self) -> u8 {
+        let mut channel = 0;
+        unsafe {
+            wifi_mgmr::wifi_mgmr_channel_get(&mut channel);
+        }
+        channel as u8
+    }
+}
+
+impl<F: FnMut(&[u8]) + 'static> Drop for Sniffer<F> {
+    fn drop(&mut self) {
+        unsafe {
+            wifi_mgmr::wifi_mgmr_sniffer_unregister(self.callback as *mut c_void);
+            wifi_mgmr::wifi_mgmr_sniffer_disable();
+        }
+
+        riscv::interrupt::free(|_| unsafe {
+            SNIFFER_ACTIVE = false;
+        });
+    }
+}
+
+unsafe extern "C" fn trampoline<F: FnMut(&[u8])>(env: *mut c_void, pkt: *mut u8, len: i32) {
+    if env.is_null() || pkt.is_null() || len <= 0 {
+        return;
+    }
+
+    let callback = &mut *(env as *mut F);
+    callback(core::slice::from_raw_parts(pkt, len as usize));
+}
@@ -0,0 +1,191 @@
+use smoltcp::dhcp::{Dhcpv4Client, Dhcpv4Config};
+use smoltcp::iface::{
+    EthernetInterface, EthernetInterfaceBuilder, Neighbor, NeighborCache, Route, Routes,
+};
+use smoltcp::socket::{RawPacketMetadata, RawSocketBuffer, SocketSet};
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address, Ipv4Cidr};
+
+use crate::log;
+use crate::timer::timestamp;
+
+use super::{
+    disconnect_count, is_link_up, mac_address, report_dhcp_config, trigger_transmit_if_needed,
+    WifiDevice,
+};
+
+const NEIGHBOR_CACHE_SIZE: usize = 8;
+const DHCP_RX_BUFFER_SIZE: usize = 900;
+const DHCP_TX_BUFFER_SIZE: usize = 600;
+
+/// Memory used by a [NetworkStack]. It has to outlive the stack, e.g. create it at
+/// the beginning of `main`.
+pub struct NetworkResources {
+    neighbor_cache: [Option<(IpAddress, Neighbor)>; NEIGHBOR_CACHE_SIZE],
+    ip_addrs: [IpCidr; 1],
+    routes: [Option<(IpCidr, Route)>; 1],
+    dhcp_rx_metadata: [RawPacketMetadata; 1],
+    dhcp_rx_buffer: [u8; DHCP_RX_BUFFER_SIZE],
+    dhcp_tx_metadata: [RawPacketMetadata; 1],
+    dhcp_tx_buffer: [u8; DHCP_TX_BUFFER_SIZE],
+}
+
+impl NetworkResources {
+    pub fn new() -> NetworkResources {
+        NetworkResources {
+            neighbor_cache: [None; NEIGHBOR_CACHE_SIZE],
+            ip_addrs: [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)],
+            routes: [None; 1],
+            dhcp_rx_metadata: [RawPacketMetadata::EMPTY; 1],
+            dhcp_rx_buffer: [0u8; DHCP_RX_BUFFER_SIZE],
+            dhcp_tx_metadata: [RawPacketMetadata::EMPTY; 1],
+            dhcp_tx_buffer: [0u8; DHCP_TX_BUFFER_SIZE],
+        }
+    }
+}
+
+impl Default for NetworkResources {
+    fn default() -> Self {
+        NetworkResources::new()
+    }
+}
+
+/// The IP configuration we got via DHCP
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct IpConfig {
+    pub address: Option<Ipv4Cidr>,
+    pub gateway: Option<Ipv4Address>,
+    pub dns_servers: [Option<Ipv4Address>; 3],
+}
+
+/// A smoltcp interface on the station which gets its address via DHCP.
+/// DHCP restarts whenever the link goes down and comes back up, also if
+/// that happened in between two polls.
+pub struct NetworkStack<'a> {
+    interface: EthernetInterface<'a, WifiDevice>,
+    sockets: SocketSet<'a>,
+    dhcp: Dhcpv4Client,
+    ip_config: IpConfig,
+    link_up: bool,
+    disconnects: u32,
+}
+
+impl<'a> NetworkStack<'a> {
+    /// Creates the stack, call this after `wifi_init`. The socket set needs a
+    /// free entry for the DHCP client's socket.
+    pub fn new(
+        resources: &'a mut NetworkResources,
+        mut sockets: SocketSet<'a>,
+    ) -> NetworkStack<'a> {
+        let device = WifiDevice::new();
+        let mac = mac_address(device.interface());
+
+        let dhcp_rx_buffer = RawSocketBuffer::new(
+            &mut resources.dhcp_rx_metadata[..],
+            &mut resources.dhcp_rx_buffer[..],
+        );
+        let dhcp_tx_buffer = RawSocketBuffer::new(
+            &mut resources.dhcp_tx_metadata[..],
+            &mut resources.dhcp_tx_buffer[..],
+        );
+        let dhcp = Dhcpv4Client::new(&mut sockets, dhcp_rx_buffer, dhcp_tx_buffer, timestamp());
+
+        let interface = EthernetInterfaceBuilder::new(device)
+            .ethernet_addr(EthernetAddress::from_bytes(&mac))
+            .neighbor_cache(NeighborCache::new(&mut resources.neighbor_cache[..]))
+            .ip_addrs(&mut resources.ip_addrs[..])
+            .routes(Routes::new(&mut resources.routes[..]))
+            .finalize();
+
+        NetworkStack {
+            interface,
+            sockets,
+            dhcp,
+            ip_config: IpConfig::default(),
+            link_up: false,
+            disconnects: disconnect_count(),
+        }
+    }
+
+    /// Processes received and queued frames and runs DHCP. Call this regularly.
+    pub fn poll(&mut self) {
+        let now = timestamp();
+
+        let link_up = is_link_up();
+        let disconnects = disconnect_count();
+        if link_up != self.link_up || disconnects != self.disconnects {
+            self.link_up = link_up;
+            self.disconnects = disconnects;
+            self.clear_ip_config();
+            // start over right away instead of waiting for the lease to time out
+            self.dhcp.reset(now);
+        }
+
+        let interface = &mut self.interface;
+        let sockets = &mut self.sockets;
+        riscv::interrupt::free(|_| {
+            interface.poll(sockets, now).ok();
+        });
+
+        trigger_transmit_if_needed();
+
+        if !link_up {
+            return;
+        }
+
+        match self.dhcp.poll(&mut self.interface, &mut self.sockets, now) {
+            Ok(Some(config)) => self.apply_dhcp_config(&config),
+            Ok(None) => (),
+            Err(err) => log!("DHCP: {:?}", err),
+        }
+    }
+
+    pub fn ip_config(&self) -> IpConfig {
+        self.ip_config
+    }
+
+    pub fn sockets(&mut self) -> &mut SocketSet<'a> {
+        &mut self.sockets
+    }
+
+    pub fn interface(&mut self) -> &mut EthernetInterface<'a, WifiDevice> {
+        &mut self.interface
+    }
+
+    fn apply_dhcp_config(&mut self, config: &Dhcpv4Config) {
+        if let Some(cidr) = config.address {
+            if Some(cidr) != self.ip_config.address {
+                self.interface.update_ip_addrs(|addrs| {
+                    if let Some(addr) = addrs.iter_mut().next() {
+                        *addr = IpCidr::Ipv4(cidr);
+                    }
+                });
+                self.ip_config.address = Some(cidr);
+                log!("Assigned a new IPv4 address: {}", cidr);
+            }
+        }
+
+        if let Some(router) = config.router {
+            self.interface
+                .routes_mut()
+                .add_default_ipv4_route(router)
+                .ok();
+            self.ip_config.gateway = Some(router);
+        }
+
+        if config.dns_servers.iter().any(|server| server.is_some()) {
+            self.ip_config.dns_servers = config.dns_servers;
+        }
+
+        report_dhcp_config(config).ok();
+    }
+
+    fn clear_ip_config(&mut self) {
+        self.interface.update_ip_addrs(|addrs| {
+            if let Some(addr) = addrs.iter_mut().next() {
+                *addr = IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0);
+            }
+        });
+        self.interface.routes_mut().update(|routes| routes.clear());
+        self.ip_config = IpConfig::default();
+    }
+}
@@ -0,0 +1,263 @@
+use crate::binary::wifi_mgmr::{
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTED_IP_GETTING,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTED_IP_GOT, WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTING,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_DISCONNECT, WIFI_STATE_ENUM_LIST_WIFI_STATE_IDLE,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_IFDOWN, WIFI_STATE_ENUM_LIST_WIFI_STATE_NO_AP_FOUND,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_PSK_ERROR, WIFI_STATE_ENUM_LIST_WIFI_STATE_SNIFFER,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_UNKNOWN,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTED_IP_GETTING,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTED_IP_GOT,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTING,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_DISCONNECT,
+    WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_IDLE,
+};
+use crate::timer::timestamp;
+use smoltcp::time::Instant;
+
+use super::WifiEvent;
+
+/// Number of state transitions kept in the history
+pub const STATE_HISTORY_SIZE: usize = 16;
+
+static mut STATE_HISTORY: StateHistory = StateHistory::new();
+// latest event since the state was last checked
+static mut PENDING_EVENT: Option<WifiEvent> = None;
+
+/// State of the blob's wifi manager. The `WithAp` states are the same as
+/// their counterparts while our AP is running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WifiState {
+    Unknown,
+    Idle,
+    Connecting,
+    ConnectedIpGetting,
+    ConnectedIpGot,
+    Disconnect,
+    WithApIdle,
+    WithApConnecting,
+    WithApConnectedIpGetting,
+    WithApConnectedIpGot,
+    WithApDisconnect,
+    IfDown,
+    Sniffer,
+    PskError,
+    NoApFound,
+    Other(i32),
+}
+
+impl WifiState {
+    pub fn from_raw(state: i32) -> WifiState {
+        match state {
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_UNKNOWN => WifiState::Unknown,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_IDLE => WifiState::Idle,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTING => WifiState::Connecting,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTED_IP_GETTING => WifiState::ConnectedIpGetting,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_CONNECTED_IP_GOT => WifiState::ConnectedIpGot,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_DISCONNECT => WifiState::Disconnect,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_IDLE => WifiState::WithApIdle,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTING => WifiState::WithApConnecting,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTED_IP_GETTING => {
+                WifiState::WithApConnectedIpGetting
+            }
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_CONNECTED_IP_GOT => {
+                WifiState::WithApConnectedIpGot
+            }
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_WITH_AP_DISCONNECT => WifiState::WithApDisconnect,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_IFDOWN => WifiState::IfDown,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_SNIFFER => WifiState::Sniffer,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_PSK_ERROR => WifiState::PskError,
+            WIFI_STATE_ENUM_LIST_WIFI_STATE_NO_AP_FOUND => WifiState::NoApFound,
+            other => WifiState::Other(other),
+        }
+    }
+
+    /// True while our AP is running
+    pub fn is_ap_running(&self) -> bool {
+        matches!(
+            self,
+            WifiState::WithApIdle
+                | WifiState::WithApConnecting
+                | WifiState::WithApConnectedIpGetting
+                | WifiState::WithApConnectedIpGot
+                | WifiState::WithApDisconnect
+        )
+    }
+
+    /// True if the station is associated, with or without an IP address
+    pub fn is_sta_connected(&self) -> bool {
+        matches!(
+            self,
+            WifiState::ConnectedIpGetting
+                | WifiState::ConnectedIpGot
+                | WifiState::WithApConnectedIpGetting
+                | WifiState::WithApConnectedIpGot
+        )
+    }
+}
+
+/// Current state of the blob's wifi manager
+pub fn wifi_state() -> WifiState {
+    let mut state = 0;
+    unsafe {
+        crate::binary::wifi_mgmr::wifi_mgmr_state_get(&mut state);
+    }
+    WifiState::from_raw(state)
+}
+
+/// A change of the `WifiState` and the latest event before it was noticed
+#[derive(Debug, Clone, Copy)]
+pub struct StateTransition {
+    pub timestamp: Instant,
+    pub from: WifiState,
+    pub to: WifiState,
+    pub event: WifiEvent,
+}
+
+/// Ring of the latest transitions, the oldest get overwritten.
+/// All methods must be called with interrupts disabled.
+struct StateHistory {
+    transitions: [Option<StateTransition>; STATE_HISTORY_SIZE],
+    write_index: usize,
+    last_state: WifiState,
+}
+
+impl StateHistory {
+    const fn new() -> StateHistory {
+        StateHistory {
+            transitions: [None; STATE_HISTORY_SIZE],
+            write_index: 0,
+            last_state: WifiState::Unknown,
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, state: WifiState, event: WifiEvent) {
+        if state == self.last_state {
+            return;
+        }
+
+        self.transitions[self.write_index] = Some(StateTransition {
+            timestamp,
+            from: self.last_state,
+            to: state,
+            event,
+        });
+        self.write_index = (self.write_index + 1) % STATE_HISTORY_SIZE;
+        self.last_state = state;
+    }
+
+    /// Oldest transition first
+    fn transitions(&self) -> [Option<StateTransition>; STATE_HISTORY_SIZE] {
+        let mut result = [None; STATE_HISTORY_SIZE];
+        for (i, entry) in result.iter_mut().enumerate() {
+            *entry = self.transitions[(self.write_index + i) % STATE_HISTORY_SIZE];
+        }
+
+        // move the empty slots of a not yet full ring to the end
+        let first = result.iter().position(|entry| entry.is_some()).unwrap_or(0);
+        result.rotate_left(first);
+        result
+    }
+
+    fn clear(&mut self) {
+        self.transitions = [None; STATE_HISTORY_SIZE];
+        self.write_index = 0;
+    }
+}
+
+/// The latest state transitions, oldest first
+pub fn state_history() -> [Option<StateTransition>; STATE_HISTORY_SIZE] {
+    riscv::interrupt::free(|_| unsafe { STATE_HISTORY.transitions() })
+}
+
+pub fn clear_state_history() {
+    riscv::interrupt::free(|_| unsafe {
+        STATE_HISTORY.clear();
+    });
+}
+
+/// Must be called with interrupts disabled.
+pub(crate) unsafe fn on_event(event: WifiEvent) {
+    PENDING_EVENT = Some(event);
+}
+
+/// Records a transition if the state changed since the last event.
+/// Called from the network loop, the blob can't be queried from its event callback.
+pub(crate) fn poll() {
+    let event = riscv::interrupt::free(|_| unsafe { PENDING_EVENT.take() });
+
+    if let Some(event) = event {
+        let state = wifi_state();
+
+        riscv::interrupt::free(|_| unsafe {
+            STATE_HISTORY.record(timestamp(), state, event);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: i64) -> Instant {
+        Instant::from_millis(millis)
+    }
+
+    #[test]
+    fn record_skips_unchanged_state() {
+        let mut history = StateHistory::new();
+        history.record(at(10), WifiState::Unknown, WifiEvent::InitDone);
+        history.record(at(20), WifiState::Idle, WifiEvent::MgmrDone);
+        history.record(at(30), WifiState::Idle, WifiEvent::ScanDone);
+        history.record(at(40), WifiState::Connecting, WifiEvent::Connecting);
+
+        let transitions = history.transitions();
+        assert_eq!(transitions.iter().flatten().count(), 2);
+
+        let first = transitions[0].unwrap();
+        assert_eq!(first.from, WifiState::Unknown);
+        assert_eq!(first.to, WifiState::Idle);
+        assert_eq!(first.event, WifiEvent::MgmrDone);
+        assert_eq!(first.timestamp, at(20));
+
+        let second = transitions[1].unwrap();
+        assert_eq!(second.from, WifiState::Idle);
+        assert_eq!(second.to, WifiState::Connecting);
+        assert_eq!(second.timestamp, at(40));
+    }
+
+    #[test]
+    fn oldest_first_after_wrapping() {
+        let mut history = StateHistory::new();
+        for i in 0..STATE_HISTORY_SIZE as i32 + 5 {
+            history.record(at(0), WifiState::Other(i), WifiEvent::Reconnect);
+        }
+
+        let transitions = history.transitions();
+        for (i, transition) in transitions.iter().enumerate() {
+            assert_eq!(transition.unwrap().to, WifiState::Other(i as i32 + 5));
+        }
+    }
+
+    #[test]
+    fn clear() {
+        let mut history = StateHistory::new();
+        history.record(at(0), WifiState::Idle, WifiEvent::MgmrDone);
+        history.clear();
+        assert!(history.transitions().iter().all(|entry| entry.is_none()));
+
+        // the state before clearing is still known
+        history.record(at(0), WifiState::Idle, WifiEvent::ScanDone);
+        assert!(history.transitions().iter().all(|entry| entry.is_none()));
+        history.record(at(0), WifiState::Connecting, WifiEvent::Connecting);
+        assert_eq!(history.transitions()[0].unwrap().from, WifiState::Idle);
+    }
+
+    #[test]
+    fn ap_and_sta_states() {
+        assert!(WifiState::WithApConnectedIpGot.is_ap_running());
+        assert!(WifiState::WithApConnectedIpGot.is_sta_connected());
+        assert!(!WifiState::ConnectedIpGot.is_ap_running());
+        assert!(WifiState::ConnectedIpGetting.is_sta_connected());
+        assert!(!WifiState::WithApIdle.is_sta_connected());
+    }
+}
@@ -0,0 +1,245 @@
+//! One line per frame passing the network stack, only built with the
+//! `packet-trace` feature. Printing goes through the blocking writer so
+//! filter to the traffic of interest.
+
+use core::fmt;
+
+use smoltcp::wire::{
+    ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, IpProtocol, Ipv4Address,
+    Ipv4Packet, TcpPacket, UdpPacket,
+};
+
+use super::WifiInterface;
+use crate::println;
+
+/// Frames matching all the given fields get traced, `None` matches everything
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceFilter {
+    pub ethertype: Option<EthernetProtocol>,
+    /// Only IPv4 frames of this protocol
+    pub ip_protocol: Option<IpProtocol>,
+    /// Only TCP and UDP frames with this source or destination port
+    pub port: Option<u16>,
+}
+
+impl TraceFilter {
+    /// Matches every frame
+    pub const ALL: TraceFilter = TraceFilter {
+        ethertype: None,
+        ip_protocol: None,
+        port: None,
+    };
+
+    fn matches(&self, summary: &FrameSummary) -> bool {
+        if let Some(ethertype) = self.ethertype {
+            if summary.ethertype != ethertype {
+                return false;
+            }
+        }
+
+        if let Some(protocol) = self.ip_protocol {
+            match summary.network {
+                Network::Ipv4 { protocol: p, .. } if p == protocol => (),
+                _ => return false,
+            }
+        }
+
+        if let Some(port) = self.port {
+            match summary.network {
+                Network::Ipv4 {
+                    ports: Some((src, dst)),
+                    ..
+                } if src == port || dst == port => (),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl Default for TraceFilter {
+    fn default() -> Self {
+        TraceFilter::ALL
+    }
+}
+
+static mut TRACE_FILTER: Option<TraceFilter> = Some(TraceFilter::ALL);
+
+/// Sets the filter, `None` stops tracing. Everything is traced by default.
+pub fn set_packet_trace(filter: Option<TraceFilter>) {
+    riscv::interrupt::free(|_| unsafe {
+        TRACE_FILTER = filter;
+    });
+}
+
+pub fn packet_trace() -> Option<TraceFilter> {
+    riscv::interrupt::free(|_| unsafe { TRACE_FILTER })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Direction {
+    Rx,
+    Tx,
+}
+
+/// Prints e.g. `rx sta 00-11-22-33-44-55>ff-ff-ff-ff-ff-ff UDP 0.0.0.0:68>255.255.255.255:67 len=342`
+pub(crate) fn trace_frame(direction: Direction, interface: WifiInterface, frame: &[u8]) {
+    let filter = match packet_trace() {
+        Some(filter) => filter,
+        None => return,
+    };
+
+    let summary = match FrameSummary::parse(direction, interface, frame) {
+        Some(summary) => summary,
+        None => {
+            // nothing to match a narrower filter against
+            if filter == TraceFilter::ALL {
+                println!(
+                    "{} {} malformed len={}",
+                    direction,
+                    interface_name(interface),
+                    frame.len()
+                );
+            }
+            return;
+        }
+    };
+
+    if filter.matches(&summary) {
+        println!("{}", summary);
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Rx => f.write_str("rx"),
+            Direction::Tx => f.write_str("tx"),
+        }
+    }
+}
+
+fn interface_name(interface: WifiInterface) -> &'static str {
+    match interface {
+        WifiInterface::Sta => "sta",
+        WifiInterface::Ap => "ap",
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Network {
+    Ipv4 {
+        src: Ipv4Address,
+        dst: Ipv4Address,
+        protocol: IpProtocol,
+        ports: Option<(u16, u16)>,
+    },
+    Arp {
+        src: Ipv4Address,
+        dst: Ipv4Address,
+    },
+    Other,
+}
+
+struct FrameSummary {
+    direction: Direction,
+    interface: WifiInterface,
+    src: EthernetAddress,
+    dst: EthernetAddress,
+    ethertype: EthernetProtocol,
+    network: Network,
+    len: usize,
+}
+
+impl FrameSummary {
+    /// `None` if the ethernet header is incomplete, higher layers which
+    /// don't parse are shown by their ethertype only
+    fn parse(direction: Direction, interface: WifiInterface, frame: &[u8]) -> Option<Self> {
+        let ethernet = EthernetFrame::new_checked(frame).ok()?;
+
+        let network = match ethernet.ethertype() {
+            EthernetProtocol::Ipv4 => Self::parse_ipv4(ethernet.payload()),
+            EthernetProtocol::Arp => Self::parse_arp(ethernet.payload()),
+            _ => Network::Other,
+        };
+
+        Some(FrameSummary {
+            direction,
+            interface,
+            src: ethernet.src_addr(),
+            dst: ethernet.dst_addr(),
+            ethertype: ethernet.ethertype(),
+            network,
+            len: frame.len(),
+        })
+    }
+
+    fn parse_ipv4(payload: &[u8]) -> Network {
+        let ip = match Ipv4Packet::new_checked(payload) {
+            Ok(ip) => ip,
+            Err(_) => return Network::Other,
+        };
+
+        let ports = match ip.protocol() {
+            IpProtocol::Tcp => TcpPacket::new_checked(ip.payload())
+                .ok()
+                .map(|tcp| (tcp.src_port(), tcp.dst_port())),
+            IpProtocol::Udp => UdpPacket::new_checked(ip.payload())
+                .ok()
+                .map(|udp| (udp.src_port(), udp.dst_port())),
+            _ => None,
+        };
+
+        Network::Ipv4 {
+            src: ip.src_addr(),
+            dst: ip.dst_addr(),
+            protocol: ip.protocol(),
+            ports,
+        }
+    }
+
+    fn parse_arp(payload: &[u8]) -> Network {
+        let repr = ArpPacket::new_checked(payload).and_then(|arp| ArpRepr::parse(&arp));
+        match repr {
+            Ok(ArpRepr::EthernetIpv4 {
+                source_protocol_addr,
+                target_protocol_addr,
+                ..
+            }) => Network::Arp {
+                src: source_protocol_addr,
+                dst: target_protocol_addr,
+            },
+            _ => Network::Other,
+        }
+    }
+}
+
+impl fmt::Display for FrameSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}>{} ",
+            self.direction,
+            interface_name(self.interface),
+            self.src,
+            self.dst
+        )?;
+
+        match self.network {
+            Network::Ipv4 {
+                src,
+                dst,
+                protocol,
+                ports: Some((src_port, dst_port)),
+            } => write!(f, "{} {}:{}>{}:{}", protocol, src, src_port, dst, dst_port)?,
+            Network::Ipv4 {
+                src, dst, protocol, ..
+            } => write!(f, "{} {}>{}", protocol, src, dst)?,
+            Network::Arp { src, dst } => write!(f, "ARP {}>{}", src, dst)?,
+            Network::Other => write!(f, "{}", self.ethertype)?,
+        }
+
+        write!(f, " len={}", self.len)
+    }
+}
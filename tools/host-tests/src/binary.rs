@@ -0,0 +1,6 @@
+#[path = "../../../src/binary/c_types.rs"]
+pub mod c_types;
+#[path = "../../../src/binary/wifi_mgmr.rs"]
+pub mod wifi_mgmr;
+#[path = "../../../src/binary/wifi_mgmr_api.rs"]
+pub mod wifi_mgmr_api;
@@ -0,0 +1,98 @@
+//! Runs the unit tests of the modules which don't need the chip on the host:
+//!
+//! ```text
+//! cd tools/host-tests
+//! cargo test --target <host triple>
+//! ```
+//!
+//! The modules are included from `src` of the main crate. The crates and
+//! modules they use are replaced by the minimal shims below.
+
+#![allow(dead_code)]
+// newer toolchains warn about the `static mut` pattern used all over the crate
+// and suggest APIs the crate's toolchain doesn't have yet
+#![allow(unknown_lints, static_mut_refs)]
+#![allow(
+    clippy::derivable_impls,
+    clippy::manual_is_multiple_of,
+    clippy::unnecessary_map_or
+)]
+
+// the shims below stand in for these crates
+extern crate self as embedded_time;
+extern crate self as nb;
+extern crate self as riscv;
+extern crate self as smoltcp;
+
+#[allow(clippy::all, warnings)]
+mod binary;
+pub mod storage;
+pub mod wifi;
+
+mod compat {
+    pub fn get_time() -> crate::duration::Milliseconds {
+        crate::duration::Milliseconds(0)
+    }
+}
+
+mod timer {
+    pub fn timestamp() -> crate::time::Instant {
+        crate::time::Instant::from_millis(0)
+    }
+}
+
+// riscv
+pub mod interrupt {
+    pub struct CriticalSection;
+
+    pub fn free<F, R>(f: F) -> R
+    where
+        F: FnOnce(&CriticalSection) -> R,
+    {
+        f(&CriticalSection)
+    }
+}
+
+// nb
+#[derive(Debug)]
+pub enum Error<E> {
+    Other(E),
+    WouldBlock,
+}
+
+pub type Result<T, E> = core::result::Result<T, Error<E>>;
+
+#[macro_export]
+macro_rules! block {
+    ($e:expr) => {
+        loop {
+            match $e {
+                Err($crate::Error::Other(e)) => break Err(e),
+                Err($crate::Error::WouldBlock) => (),
+                Ok(x) => break Ok(x),
+            }
+        }
+    };
+}
+
+// embedded_time
+pub mod duration {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Milliseconds(pub u32);
+}
+
+// smoltcp
+pub mod time {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Instant {
+        millis: i64,
+    }
+
+    impl Instant {
+        pub fn from_millis<T: Into<i64>>(millis: T) -> Instant {
+            Instant {
+                millis: millis.into(),
+            }
+        }
+    }
+}
@@ -0,0 +1,6 @@
+#[path = "../../../src/storage/flash.rs"]
+mod flash;
+pub use flash::{Flash, FlashError, MemFlash};
+
+#[path = "../../../src/storage/kv.rs"]
+pub mod kv;
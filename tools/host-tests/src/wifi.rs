@@ -0,0 +1,23 @@
+#[path = "../../../src/wifi/connect.rs"]
+pub mod connect;
+#[path = "../../../src/wifi/country.rs"]
+pub mod country;
+#[path = "../../../src/wifi/event.rs"]
+pub mod event;
+#[path = "../../../src/wifi/ieee80211.rs"]
+pub mod ieee80211;
+#[path = "../../../src/wifi/mac.rs"]
+pub mod mac;
+#[path = "../../../src/wifi/psk.rs"]
+pub mod psk;
+#[path = "../../../src/wifi/state.rs"]
+pub mod state;
+
+pub use country::regulatory_domain;
+pub use event::WifiEvent;
+
+pub static mut STA_DOWN_REQUESTED: bool = false;
+
+pub fn str_from_c(_ptr: *const u8) -> &'static str {
+    ""
+}
@@ -0,0 +1,145 @@
+//! Generates rfparams.ld from a TOML description and prints existing rfparams.
+//!
+//! ```text
+//! cargo run --target <host triple> -- build ../../rfparams.toml ../../rfparams.ld
+//! cargo run --target <host triple> -- dump ../../rfparams.ld
+//! ```
+
+use std::{env, fmt::Write as _, fs, process};
+
+#[path = "../../../src/rfparams/tlv.rs"]
+#[allow(dead_code)]
+mod tlv;
+
+use tlv::{Format, RfParams, RfParamsWriter, Tag, RFPARAMS_LEN, TAGS};
+
+const USAGE: &str = "usage: rfparams build <description.toml> <rfparams.ld> [--bin <rfparams>]
+       rfparams dump <rfparams.ld | rfparams>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>()[..] {
+        ["build", description, ld] => build(description, ld, None),
+        ["build", description, ld, "--bin", bin] => build(description, ld, Some(bin)),
+        ["dump", file] => dump(file),
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn build(description: &str, ld: &str, bin: Option<&str>) -> Result<(), String> {
+    let text =
+        fs::read_to_string(description).map_err(|err| format!("{}: {}", description, err))?;
+    let table = match text.parse::<toml::Value>() {
+        Ok(toml::Value::Table(table)) => table,
+        Ok(_) => return Err(format!("{}: expected a table", description)),
+        Err(err) => return Err(format!("{}: {}", description, err)),
+    };
+
+    if let Some(key) = table.keys().find(|key| Tag::from_name(key).is_none()) {
+        return Err(format!("{}: unknown key `{}`", description, key));
+    }
+
+    let mut data = vec![0u8; RFPARAMS_LEN];
+    let mut writer = RfParamsWriter::new(&mut data).map_err(|err| format!("{:?}", err))?;
+
+    // the order of the tags, not the one of the description
+    for tag in TAGS.iter() {
+        if let Some(value) = table.get(tag.name()) {
+            let bytes = encode(*tag, value)?;
+            writer
+                .entry(*tag, &bytes)
+                .map_err(|err| format!("{}: {:?}", tag.name(), err))?;
+        }
+    }
+    writer.finish();
+
+    // make sure the firmware will be able to read it
+    RfParams::parse(&data).map_err(|err| format!("{:?}", err))?;
+
+    let mut script = String::new();
+    for byte in &data {
+        writeln!(script, "BYTE(0x{:02X})", byte).unwrap();
+    }
+    fs::write(ld, script).map_err(|err| format!("{}: {}", ld, err))?;
+
+    if let Some(bin) = bin {
+        fs::write(bin, &data).map_err(|err| format!("{}: {}", bin, err))?;
+    }
+
+    Ok(())
+}
+
+fn encode(tag: Tag, value: &toml::Value) -> Result<Vec<u8>, String> {
+    let format = tag.format();
+
+    if format == Format::Str {
+        return match value.as_str() {
+            Some(value) if value.is_ascii() => Ok(value.as_bytes().to_vec()),
+            _ => Err(format!("{}: expected an ASCII string", tag.name())),
+        };
+    }
+
+    let elements = match value {
+        toml::Value::Integer(_) => vec![value.clone()],
+        toml::Value::Array(elements) => elements.clone(),
+        _ => return Err(format!("{}: expected a number or an array", tag.name())),
+    };
+
+    let max = match format.width() {
+        1 => u8::MAX as i64,
+        2 => u16::MAX as i64,
+        _ => u32::MAX as i64,
+    };
+
+    let mut bytes = Vec::new();
+    for element in elements {
+        let element = match element.as_integer() {
+            Some(element) if (0..=max).contains(&element) => element as u32,
+            _ => return Err(format!("{}: elements have to be 0 to {}", tag.name(), max)),
+        };
+        bytes.extend_from_slice(&element.to_le_bytes()[..format.width()]);
+    }
+
+    tag.validate_len(bytes.len())
+        .map_err(|_| match tag.count() {
+            Some(count) => format!("{}: expected {} elements", tag.name(), count),
+            None => format!("{}: invalid length", tag.name()),
+        })?;
+
+    Ok(bytes)
+}
+
+fn dump(file: &str) -> Result<(), String> {
+    let raw = fs::read(file).map_err(|err| format!("{}: {}", file, err))?;
+
+    let data = if file.ends_with(".ld") {
+        parse_linker_script(&String::from_utf8_lossy(&raw))?
+    } else {
+        raw
+    };
+
+    let params = RfParams::parse(&data).map_err(|err| format!("{}: {:?}", file, err))?;
+    print!("{}", params);
+    Ok(())
+}
+
+/// Reads the bytes of the `BYTE(0x..)` statements
+fn parse_linker_script(script: &str) -> Result<Vec<u8>, String> {
+    script
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.strip_prefix("BYTE(0x")
+                .and_then(|line| line.strip_suffix(')'))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("unexpected line `{}`", line))
+        })
+        .collect()
+}